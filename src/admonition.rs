@@ -0,0 +1,294 @@
+//! admonition.rs — Callout boxes: GitHub-style alerts (`> [!NOTE]`) and
+//!                 `:::note` fenced containers.
+//!
+//! Containers are rewritten into alert blockquotes on the markdown source, so
+//! both syntaxes reach the parsed events as blockquotes whose first line is
+//! `[!KIND]`; those blockquotes become `<div class="admonition">` boxes with
+//! an icon and a title.
+
+use crate::renderer::{escape_html, find_code_regions, is_chinese};
+use pulldown_cmark::{html, Event, Tag};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Kind of callout; each has its own colour and icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl Kind {
+    /// GitHub's alert names, plus the usual container aliases
+    /// (`info`, `hint`, `attention`, `danger`, `error`).
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "note" | "info" => Some(Kind::Note),
+            "tip" | "hint" => Some(Kind::Tip),
+            "important" => Some(Kind::Important),
+            "warning" | "attention" => Some(Kind::Warning),
+            "caution" | "danger" | "error" => Some(Kind::Caution),
+            _ => None,
+        }
+    }
+
+    fn class(self) -> &'static str {
+        match self {
+            Kind::Note => "note",
+            Kind::Tip => "tip",
+            Kind::Important => "important",
+            Kind::Warning => "warning",
+            Kind::Caution => "caution",
+        }
+    }
+
+    /// Default title, in the document language.
+    fn title(self, chinese: bool) -> &'static str {
+        match (self, chinese) {
+            (Kind::Note, false) => "Note",
+            (Kind::Tip, false) => "Tip",
+            (Kind::Important, false) => "Important",
+            (Kind::Warning, false) => "Warning",
+            (Kind::Caution, false) => "Caution",
+            (Kind::Note, true) => "备注",
+            (Kind::Tip, true) => "提示",
+            (Kind::Important, true) => "重要",
+            (Kind::Warning, true) => "警告",
+            (Kind::Caution, true) => "注意",
+        }
+    }
+
+    /// Inline SVG drawn in `currentColor`, so it prints without icon fonts.
+    fn icon(self) -> &'static str {
+        match self {
+            Kind::Note => r#"<circle cx="8" cy="8" r="6.5"/><path d="M8 7v4.5M8 4.5v.5"/>"#,
+            Kind::Tip => r#"<path d="M6 12.5h4M6.5 14.5h3M8 1.5a4.5 4.5 0 0 0-2.5 8.2V11h5V9.7A4.5 4.5 0 0 0 8 1.5z"/>"#,
+            Kind::Important => r#"<path d="M2 2.5h12v8.5H7.5L4.5 14v-3H2z"/><path d="M8 4.5v3.5M8 9.5v.5"/>"#,
+            Kind::Warning => r#"<path d="M8 1.5L15 14H1z"/><path d="M8 6v4M8 11.5v.5"/>"#,
+            Kind::Caution => r#"<path d="M5.3 1.5h5.4l3.8 3.8v5.4l-3.8 3.8H5.3l-3.8-3.8V5.3z"/><path d="M8 4.5v4.5M8 10.5v.5"/>"#,
+        }
+    }
+}
+
+fn icon_svg(kind: Kind) -> String {
+    format!(
+        r#"<svg class="admonition-icon" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round" aria-hidden="true">{}</svg>"#,
+        kind.icon()
+    )
+}
+
+// ─────────────────────────────────────────────
+//  Fenced containers
+// ─────────────────────────────────────────────
+
+/// Rewrite `:::kind [title]` … `:::` containers (outside code) into alert
+/// blockquotes (`> [!KIND] title`, every line quoted).  Containers nest; a
+/// closing `:::` becomes a blank line so the quote cannot swallow the text
+/// after it.  The generated `>` lines keep the opening fence's indentation,
+/// so a container inside a list item stays in it.  Unknown kinds are left
+/// alone.
+pub(crate) fn fence_containers(text: &str) -> String {
+    if !text.contains(":::") {
+        return text.to_string();
+    }
+    static OPEN: OnceLock<Regex> = OnceLock::new();
+    static CLOSE: OnceLock<Regex> = OnceLock::new();
+    let open = OPEN.get_or_init(|| Regex::new(r"^\s*:{3,}\s*([A-Za-z]+)[ \t]*(.*?)\s*$").unwrap());
+    let close = CLOSE.get_or_init(|| Regex::new(r"^\s*:{3,}\s*$").unwrap());
+    let code = find_code_regions(text);
+
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0;
+    let mut indent = ""; // of the outermost open container
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset + (line.len() - line.trim_start().len());
+        offset += line.len();
+        let in_code = code.iter().any(|r| r.contains(&start));
+        let body = line.trim_end_matches(['\n', '\r']);
+        let newline = &line[body.len()..];
+        let quote = |indent: &str, depth: usize| format!("{}{}", indent, "> ".repeat(depth));
+        if !in_code {
+            if let Some(cap) = open.captures(body).filter(|cap| Kind::parse(&cap[1]).is_some()) {
+                if depth == 0 {
+                    indent = &body[..body.len() - body.trim_start().len()];
+                }
+                let kind = Kind::parse(&cap[1]).unwrap();
+                let marker = format!("[!{}] {}", kind.class().to_uppercase(), &cap[2]);
+                out.push_str(&format!("{}> {}{}", quote(indent, depth), marker.trim_end(), newline));
+                depth += 1;
+                continue;
+            }
+            if depth > 0 && close.is_match(body) {
+                depth -= 1;
+                out.push_str(quote(indent, depth).trim_end());
+                out.push_str(if newline.is_empty() { "\n" } else { newline });
+                continue;
+            }
+        }
+        if depth == 0 {
+            out.push_str(line);
+            continue;
+        }
+        // Content is re-indented under the quote markers, minus the fence's indent.
+        out.push_str(&quote(indent, depth));
+        out.push_str(line.strip_prefix(indent).unwrap_or_else(|| line.trim_start_matches([' ', '\t'])));
+    }
+    out
+}
+
+// ─────────────────────────────────────────────
+//  Alerts
+// ─────────────────────────────────────────────
+
+/// Turn blockquotes whose first line is `[!NOTE]`, `[!TIP]`, `[!IMPORTANT]`,
+/// `[!WARNING]` or `[!CAUTION]` into callout boxes.  Text after the marker
+/// replaces the default title, which follows `lang` (guessed from `sample`
+/// when unset).  Expects merged text events (see `number_floats`).
+pub(crate) fn render_alerts<'a>(events: Vec<Event<'a>>, lang: Option<&str>, sample: &str) -> Vec<Event<'a>> {
+    let marker = Regex::new(r"^\[!([A-Za-z]+)\][ \t]*").unwrap();
+    let is_marker = |event: Option<&Event>| {
+        matches!(event, Some(Event::Text(t)) if marker.captures(t).is_some_and(|cap| Kind::parse(&cap[1]).is_some()))
+    };
+    let starts: Vec<bool> = (0..events.len())
+        .map(|i| {
+            matches!(events[i], Event::Start(Tag::BlockQuote))
+                && matches!(events.get(i + 1), Some(Event::Start(Tag::Paragraph)))
+                && is_marker(events.get(i + 2))
+        })
+        .collect();
+    if !starts.contains(&true) {
+        return events;
+    }
+    let chinese = is_chinese(lang, sample);
+
+    let mut out = Vec::with_capacity(events.len());
+    let mut quotes: Vec<bool> = Vec::new(); // open blockquotes: is it a callout?
+    let mut events = events.into_iter().enumerate().peekable();
+    while let Some((i, event)) = events.next() {
+        match event {
+            Event::Start(Tag::BlockQuote) if starts[i] => {
+                events.next();
+                let Some((_, Event::Text(text))) = events.next() else { unreachable!() };
+                let cap = marker.captures(&text).unwrap();
+                let kind = Kind::parse(&cap[1]).unwrap();
+
+                // The title is the rest of the first line, markup included.
+                let mut title = Vec::new();
+                let rest = &text[cap[0].len()..];
+                if !rest.is_empty() {
+                    title.push(Event::Text(rest.to_string().into()));
+                }
+                let mut paragraph_done = false;
+                while let Some((_, event)) = events.peek() {
+                    match event {
+                        Event::SoftBreak | Event::HardBreak => {
+                            events.next();
+                            break;
+                        }
+                        Event::End(Tag::Paragraph) => {
+                            events.next();
+                            paragraph_done = true;
+                            break;
+                        }
+                        _ => title.extend(events.next().map(|(_, e)| e)),
+                    }
+                }
+                let title = if title.is_empty() {
+                    escape_html(kind.title(chinese))
+                } else {
+                    let mut html_title = String::new();
+                    html::push_html(&mut html_title, title.into_iter());
+                    html_title.trim().to_string()
+                };
+                out.push(Event::Html(
+                    format!(
+                        "<div class=\"admonition admonition-{}\">\n<p class=\"admonition-title\">{}<span>{}</span></p>\n",
+                        kind.class(),
+                        icon_svg(kind),
+                        title
+                    )
+                    .into(),
+                ));
+                if !paragraph_done {
+                    out.push(Event::Start(Tag::Paragraph));
+                }
+                quotes.push(true);
+            }
+            Event::Start(Tag::BlockQuote) => {
+                quotes.push(false);
+                out.push(Event::Start(Tag::BlockQuote));
+            }
+            Event::End(Tag::BlockQuote) => match quotes.pop() {
+                Some(true) => out.push(Event::Html("</div>\n".into())),
+                _ => out.push(Event::End(Tag::BlockQuote)),
+            },
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crossref::number_floats;
+    use crate::renderer::markdown_options;
+    use pulldown_cmark::Parser;
+
+    /// Markdown to HTML the way the renderer does it, text events merged.
+    fn render(markdown: &str, lang: Option<&str>) -> String {
+        let source = fence_containers(markdown);
+        let events: Vec<Event> = Parser::new_ext(&source, markdown_options()).collect();
+        let (events, _) = number_floats(events, lang, "");
+        let mut html = String::new();
+        html::push_html(&mut html, render_alerts(events, lang, markdown).into_iter());
+        html
+    }
+
+    #[test]
+    fn containers_become_quoted_alerts() {
+        let md = ":::warning Careful\ntext\n\n::: tip\ninner\n:::\n:::\nafter\n";
+        assert_eq!(
+            fence_containers(md),
+            "> [!WARNING] Careful\n> text\n> \n> > [!TIP]\n> > inner\n>\n\nafter\n"
+        );
+    }
+
+    #[test]
+    fn containers_keep_list_indentation() {
+        let md = "- item\n\n  :::note\n  body\n  :::\n";
+        assert_eq!(fence_containers(md), "- item\n\n  > [!NOTE]\n  > body\n\n");
+        let html = render(md, None);
+        assert!(html.contains("<li>\n<p>item</p>\n<div class=\"admonition admonition-note\">"), "{}", html);
+    }
+
+    #[test]
+    fn unknown_kinds_and_code_are_left_alone() {
+        let md = ":::spoiler\nx\n:::\n\n```\n:::note\n```\n";
+        assert_eq!(fence_containers(md), md);
+    }
+
+    #[test]
+    fn alerts_get_titles_in_the_document_language() {
+        let html = render("> [!TIP]\n> Use it.\n", None);
+        assert!(html.starts_with("<div class=\"admonition admonition-tip\">"), "{}", html);
+        assert!(html.contains("<span>Tip</span></p>\n<p>Use it.</p>\n</div>"), "{}", html);
+
+        let html = render("> [!caution] 小心 *热*\n> 内容\n", Some("zh"));
+        assert!(html.contains("<span>小心 <em>热</em></span>"), "{}", html);
+
+        let html = render(":::danger\n内容\n:::\n", Some("zh"));
+        assert!(html.contains("admonition-caution") && html.contains("<span>注意</span>"), "{}", html);
+    }
+
+    #[test]
+    fn plain_blockquotes_are_kept() {
+        let html = render("> quote\n\n> [!FOO] bar\n", None);
+        assert_eq!(html.matches("<blockquote>").count(), 2);
+        assert!(!html.contains("admonition"));
+    }
+}
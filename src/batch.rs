@@ -0,0 +1,210 @@
+//! batch.rs — Expand inputs (files, directories, glob patterns) into conversion
+//!            jobs and run them concurrently on one shared converter.
+
+use md2pdf_rs::{Converter, OutputFormat};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// ─────────────────────────────────────────────
+//  Input expansion
+// ─────────────────────────────────────────────
+
+/// One markdown file and the document generated from it.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+/// A markdown source plus its path relative to the root being mirrored.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    pub relative: PathBuf,
+}
+
+pub fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+        .unwrap_or(false)
+}
+
+/// Recursively collect markdown files under `dir`, skipping hidden entries.
+fn walk_markdown(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            !p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with('.'))
+        })
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            walk_markdown(&path, out)?;
+        } else if is_markdown(&path) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Leading components of a glob pattern that contain no wildcard.
+fn glob_base(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|c| !is_glob(Path::new(c.as_os_str())))
+        .collect()
+}
+
+/// Expand every input into markdown sources.
+/// Files mirror as their file name, directories and globs keep the layout
+/// below the directory / the pattern's non-wildcard prefix.
+pub fn expand_inputs(inputs: &[PathBuf]) -> Result<Vec<Source>, String> {
+    let mut sources: Vec<Source> = Vec::new();
+
+    for input in inputs {
+        let (root, files) = if is_glob(input) {
+            let pattern = input.to_string_lossy();
+            let glob_opts = glob::MatchOptions {
+                require_literal_leading_dot: true,
+                ..Default::default()
+            };
+            let matches = glob::glob_with(&pattern, glob_opts)
+                .map_err(|e| format!("无效的 glob 模式 {}: {}", pattern, e))?;
+            let files: Vec<PathBuf> = matches
+                .filter_map(Result::ok)
+                .filter(|p| p.is_file() && is_markdown(p))
+                .collect();
+            if files.is_empty() {
+                return Err(format!("没有匹配的 Markdown 文件: {}", pattern));
+            }
+            (glob_base(input), files)
+        } else if input.is_dir() {
+            let mut files = Vec::new();
+            walk_markdown(input, &mut files)
+                .map_err(|e| format!("无法读取目录 {}: {}", input.display(), e))?;
+            if files.is_empty() {
+                return Err(format!("目录中没有 Markdown 文件: {}", input.display()));
+            }
+            (input.clone(), files)
+        } else if input.is_file() {
+            let root = input.parent().map(Path::to_path_buf).unwrap_or_default();
+            (root, vec![input.clone()])
+        } else {
+            return Err(format!("输入文件不存在: {}", input.display()));
+        };
+
+        for path in files {
+            if sources.iter().any(|s| s.path == path) {
+                continue;
+            }
+            let relative = path
+                .strip_prefix(&root)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| path.file_name().map(PathBuf::from).unwrap_or_default());
+            sources.push(Source { path, relative });
+        }
+    }
+
+    Ok(sources)
+}
+
+/// Pair sources with output paths: mirrored below `output_dir` when given,
+/// otherwise next to each source.  Two sources mapping to the same output
+/// (`a.md` and `a.markdown`, or the same relative path under two roots) are
+/// an error, since the jobs would overwrite each other.
+pub fn plan_jobs(sources: Vec<Source>, output_dir: Option<&Path>, format: OutputFormat) -> Result<Vec<Job>, String> {
+    let mut jobs: Vec<Job> = Vec::with_capacity(sources.len());
+    for s in sources {
+        let output = match output_dir {
+            Some(dir) => dir.join(&s.relative).with_extension(format.extension()),
+            None => s.path.with_extension(format.extension()),
+        };
+        if let Some(other) = jobs.iter().find(|j| j.output == output) {
+            return Err(format!(
+                "输出文件冲突: {} 和 {} 都会生成 {}",
+                other.input.display(),
+                s.path.display(),
+                output.display()
+            ));
+        }
+        jobs.push(Job { input: s.path, output });
+    }
+    Ok(jobs)
+}
+
+// ─────────────────────────────────────────────
+//  Concurrent execution
+// ─────────────────────────────────────────────
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub succeeded: Vec<Job>,
+    pub failed: Vec<(Job, String)>,
+}
+
+/// Convert all jobs with at most `parallelism` conversions in flight.
+/// Every job shares the converter's KaTeX assets and headless Chrome.
+pub async fn run(
+    converter: Converter,
+    jobs: Vec<Job>,
+    format: OutputFormat,
+    parallelism: usize,
+) -> Summary {
+    let total = jobs.len();
+    let semaphore = Arc::new(Semaphore::new(parallelism.max(1)));
+    let mut set = JoinSet::new();
+
+    for job in jobs {
+        let semaphore = semaphore.clone();
+        let converter = converter.clone();
+        set.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let task_job = job.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                if let Some(dir) = task_job.output.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                converter.convert_file(&task_job.input, &task_job.output, format)
+            })
+            .await;
+            let result = match result {
+                Ok(r) => r.map_err(|e| e.to_string()),
+                Err(e) => Err(format!("任务异常终止: {}", e)),
+            };
+            (job, result)
+        });
+    }
+
+    let mut summary = Summary::default();
+    let mut done = 0usize;
+    while let Some(joined) = set.join_next().await {
+        let Ok((job, result)) = joined else { continue };
+        done += 1;
+        match result {
+            Ok(()) => {
+                status!("[{}/{}] 完成: {}", done, total, job.input.display());
+                summary.succeeded.push(job);
+            }
+            Err(e) => {
+                eprintln!("[{}/{}] 失败: {} ({})", done, total, job.input.display(), e);
+                summary.failed.push((job, e));
+            }
+        }
+    }
+
+    summary.succeeded.sort_by(|a, b| a.input.cmp(&b.input));
+    summary.failed.sort_by(|a, b| a.0.input.cmp(&b.0.input));
+    summary
+}
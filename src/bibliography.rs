@@ -0,0 +1,1088 @@
+//! bibliography.rs — Citations: BibTeX and CSL-JSON bibliographies, `[@key]`
+//!                   citations in the markdown, and the reference list in the
+//!                   numeric, author-year or GB/T 7714 style.
+
+use crate::config::CitationStyle;
+use crate::crossref::section_level;
+use crate::renderer::{escape_html, find_code_regions};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// ─────────────────────────────────────────────
+//  References
+// ─────────────────────────────────────────────
+
+/// Kind of cited work; decides how its entry is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReferenceKind {
+    Article,
+    Book,
+    /// A chapter or article in an edited book.
+    Chapter,
+    Conference,
+    Thesis,
+    Report,
+    Webpage,
+    #[default]
+    Misc,
+}
+
+/// An author.  Organisations and names written without a given name (as is
+/// usual for Chinese names) only have `family`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Name {
+    pub family: String,
+    pub given: String,
+}
+
+/// One bibliography entry, from BibTeX or CSL-JSON.
+#[derive(Debug, Clone, Default)]
+pub struct Reference {
+    pub key: String,
+    pub kind: ReferenceKind,
+    pub authors: Vec<Name>,
+    pub title: String,
+    /// Journal, proceedings or book the work appeared in.
+    pub container: Option<String>,
+    pub year: Option<String>,
+    pub volume: Option<String>,
+    pub issue: Option<String>,
+    pub pages: Option<String>,
+    pub edition: Option<String>,
+    /// Publisher, or the university / institution of a thesis or report.
+    pub publisher: Option<String>,
+    pub place: Option<String>,
+    pub doi: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Load a bibliography: CSL-JSON for `.json` files, BibTeX otherwise.
+pub fn load_bibliography(path: &Path) -> Result<Vec<Reference>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("无法读取参考文献文件 {}: {}", path.display(), e))?;
+    let is_json = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    let parsed = if is_json { parse_csl_json(&text) } else { parse_bibtex(&text) };
+    parsed.map_err(|e| format!("无法解析参考文献文件 {}: {}", path.display(), e))
+}
+
+fn is_cjk(s: &str) -> bool {
+    s.chars().any(|c| ('\u{4e00}'..='\u{9fff}').contains(&c))
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let s = s.trim().to_string();
+    (!s.is_empty()).then_some(s)
+}
+
+// ─────────────────────────────────────────────
+//  BibTeX
+// ─────────────────────────────────────────────
+
+/// Byte length of the text up to the `}` (or `)` when `paren`) closing a
+/// block whose opening bracket has just been consumed.
+fn closing(s: &str, paren: bool) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, b) in s.bytes().enumerate() {
+        match b {
+            b'{' => depth += 1,
+            b'}' if depth == 0 && !paren => return Some(i),
+            b'}' => depth = depth.saturating_sub(1),
+            b')' if depth == 0 && paren => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `name = value` fields of an entry body.  Values may be `{braced}`,
+/// `"quoted"`, numbers or `@string` macros, joined with `#`.  Values keep
+/// their inner braces and LaTeX; see [`clean_latex`].
+fn parse_fields(body: &str, strings: &HashMap<String, String>) -> Vec<(String, String)> {
+    let bytes = body.as_bytes();
+    let mut fields = Vec::new();
+    let mut i = 0;
+    let skip_ws = |i: &mut usize| {
+        while *i < bytes.len() && (bytes[*i].is_ascii_whitespace() || bytes[*i] == b',') {
+            *i += 1;
+        }
+    };
+    loop {
+        skip_ws(&mut i);
+        let Some(eq) = body[i..].find('=') else { break };
+        let name = body[i..i + eq].trim().to_ascii_lowercase();
+        i += eq + 1;
+        let mut value = String::new();
+        loop {
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i) {
+                Some(b'{') => {
+                    let len = closing(&body[i + 1..], false).unwrap_or(body.len() - i - 1);
+                    value.push_str(&body[i + 1..i + 1 + len]);
+                    i += len + 2;
+                }
+                Some(b'"') => {
+                    let mut depth = 0;
+                    let mut j = i + 1;
+                    while j < bytes.len() && !(bytes[j] == b'"' && depth == 0) {
+                        match bytes[j] {
+                            b'{' => depth += 1,
+                            b'}' => depth -= 1,
+                            _ => {}
+                        }
+                        j += 1;
+                    }
+                    value.push_str(&body[i + 1..j.min(body.len())]);
+                    i = j + 1;
+                }
+                Some(_) => {
+                    let len = body[i..]
+                        .find(|c: char| c == ',' || c == '#' || c.is_whitespace())
+                        .unwrap_or(body.len() - i);
+                    let token = &body[i..i + len];
+                    match strings.get(&token.to_ascii_lowercase()) {
+                        Some(s) => value.push_str(s),
+                        None => value.push_str(token),
+                    }
+                    i += len;
+                }
+                None => break,
+            }
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if bytes.get(i) == Some(&b'#') {
+                i += 1;
+            } else {
+                break;
+            }
+        }
+        fields.push((name, value));
+        if i >= bytes.len() {
+            break;
+        }
+    }
+    fields
+}
+
+/// Entries of a BibTeX file.  `@string` macros are expanded, `@comment` and
+/// `@preamble` skipped.
+fn parse_bibtex(src: &str) -> Result<Vec<Reference>, String> {
+    const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+    let mut strings: HashMap<String, String> =
+        MONTHS.iter().enumerate().map(|(i, m)| (m.to_string(), (i + 1).to_string())).collect();
+    let mut refs = Vec::new();
+    let mut rest = src;
+    while let Some(at) = rest.find('@') {
+        rest = &rest[at + 1..];
+        let Some(open) = rest.find(['{', '(']) else { break };
+        let kind = rest[..open].trim().to_ascii_lowercase();
+        if !kind.chars().all(|c| c.is_ascii_alphanumeric()) {
+            continue;
+        }
+        let body_start = open + 1;
+        let len = closing(&rest[body_start..], rest.as_bytes()[open] == b'(')
+            .ok_or_else(|| format!("条目 @{} 缺少闭合括号", kind))?;
+        let body = &rest[body_start..body_start + len];
+        rest = &rest[body_start + len + 1..];
+        match kind.as_str() {
+            "comment" | "preamble" => {}
+            "string" => {
+                for (name, value) in parse_fields(body, &strings) {
+                    strings.insert(name, value);
+                }
+            }
+            _ => {
+                let (key, fields) = body.split_once(',').unwrap_or((body, ""));
+                let fields: HashMap<String, String> = parse_fields(fields, &strings).into_iter().collect();
+                refs.push(bibtex_reference(&kind, key.trim(), &fields));
+            }
+        }
+    }
+    Ok(refs)
+}
+
+fn bibtex_reference(kind: &str, key: &str, fields: &HashMap<String, String>) -> Reference {
+    let field = |names: &[&str]| names.iter().find_map(|n| fields.get(*n)).map(|v| clean_latex(v)).and_then(non_empty);
+    let names = fields.get("author").or_else(|| fields.get("editor"));
+    Reference {
+        key: key.to_string(),
+        kind: match kind {
+            "article" => ReferenceKind::Article,
+            "book" | "booklet" => ReferenceKind::Book,
+            "inbook" | "incollection" => ReferenceKind::Chapter,
+            "inproceedings" | "conference" | "proceedings" => ReferenceKind::Conference,
+            "phdthesis" | "mastersthesis" | "thesis" => ReferenceKind::Thesis,
+            "techreport" | "report" => ReferenceKind::Report,
+            "online" | "electronic" | "www" => ReferenceKind::Webpage,
+            _ => ReferenceKind::Misc,
+        },
+        authors: names.map(|n| bibtex_names(n)).unwrap_or_default(),
+        title: field(&["title"]).unwrap_or_default(),
+        container: field(&["journal", "journaltitle", "booktitle"]),
+        year: field(&["year"]).or_else(|| field(&["date"]).map(|d| d.chars().take(4).collect())),
+        volume: field(&["volume"]),
+        issue: field(&["number", "issue"]),
+        pages: field(&["pages"]),
+        edition: field(&["edition"]),
+        publisher: field(&["publisher", "school", "institution", "organization"]),
+        place: field(&["address", "location"]),
+        doi: raw_field(fields, "doi"),
+        url: raw_field(fields, "url"),
+    }
+}
+
+/// A field read verbatim, for DOIs and URLs where `~`, `--` and `%` are part
+/// of the address: only the outer braces and the `\_`, `\%`, `\#` and `\&`
+/// escapes are removed.
+fn raw_field(fields: &HashMap<String, String>, name: &str) -> Option<String> {
+    let mut value = fields.get(name)?.trim();
+    while value.starts_with('{') && closing(&value[1..], false) == Some(value.len() - 2) {
+        value = value[1..value.len() - 1].trim();
+    }
+    let mut value = value.to_string();
+    for (from, to) in [("\\_", "_"), ("\\%", "%"), ("\\#", "#"), ("\\&", "&")] {
+        value = value.replace(from, to);
+    }
+    non_empty(value)
+}
+
+/// Split `s` at `sep` where it is not inside braces.
+fn split_top_level<'a>(s: &'a str, sep: &Regex) -> Vec<&'a str> {
+    let mut depth_at = vec![0i32; s.len() + 1];
+    let mut depth = 0;
+    for (i, b) in s.bytes().enumerate() {
+        depth_at[i] = depth;
+        match b {
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            _ => {}
+        }
+    }
+    let mut parts = Vec::new();
+    let mut last = 0;
+    for m in sep.find_iter(s) {
+        if depth_at[m.start()] == 0 {
+            parts.push(&s[last..m.start()]);
+            last = m.end();
+        }
+    }
+    parts.push(&s[last..]);
+    parts
+}
+
+/// BibTeX `author` list: `Last, First and First Last and {Organisation}`.
+fn bibtex_names(list: &str) -> Vec<Name> {
+    static AND: OnceLock<Regex> = OnceLock::new();
+    static COMMA: OnceLock<Regex> = OnceLock::new();
+    static SPACE: OnceLock<Regex> = OnceLock::new();
+    let and = AND.get_or_init(|| Regex::new(r"(?i)\s+and\s+").unwrap());
+    let comma = COMMA.get_or_init(|| Regex::new(r",").unwrap());
+    let space = SPACE.get_or_init(|| Regex::new(r"\s+").unwrap());
+    split_top_level(list.trim(), and)
+        .into_iter()
+        .map(str::trim)
+        .filter(|n| !n.is_empty() && *n != "others")
+        .map(|raw| {
+            if raw.starts_with('{') && closing(&raw[1..], false) == Some(raw.len() - 2) {
+                return Name { family: clean_latex(raw), given: String::new() };
+            }
+            let parts = split_top_level(raw, comma);
+            if parts.len() > 1 {
+                return Name {
+                    family: clean_latex(parts[0]),
+                    given: clean_latex(parts[parts.len() - 1]),
+                };
+            }
+            let words = split_top_level(raw, space);
+            match words.split_last() {
+                Some((last, given)) if !given.is_empty() => Name {
+                    family: clean_latex(last),
+                    given: clean_latex(&given.join(" ")),
+                },
+                _ => Name { family: clean_latex(raw), given: String::new() },
+            }
+        })
+        .collect()
+}
+
+/// `\verb|text|` (any delimiter) replaced by its text.
+fn unwrap_verb(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(at) = rest.find("\\verb") {
+        out.push_str(&rest[..at]);
+        let after = rest[at + 5..].trim_start_matches('*');
+        let mut chars = after.chars();
+        match chars.next().filter(|c| !c.is_ascii_alphabetic()) {
+            Some(delim) => {
+                let body = chars.as_str();
+                let end = body.find(delim).unwrap_or(body.len());
+                out.push_str(&body[..end]);
+                rest = &body[(end + delim.len_utf8()).min(body.len())..];
+            }
+            None => {
+                // A longer command such as `\verbatim`; leave it to the caller.
+                out.push_str("\\verb");
+                rest = &rest[at + 5..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Plain text of a BibTeX value: accents and escapes turned into characters,
+/// `\verb` and other commands unwrapped to their argument, braces dropped.
+fn clean_latex(s: &str) -> String {
+    static ACCENTS: OnceLock<Regex> = OnceLock::new();
+    static LETTERS: OnceLock<Regex> = OnceLock::new();
+    static COMMANDS: OnceLock<Regex> = OnceLock::new();
+    // The letter-named accents (`\c`, `\v`, `\u`, `\H`) only count when no
+    // letter follows, so `\underline` or `\cite` are not read as `\u` / `\c`.
+    let accents = ACCENTS.get_or_init(|| {
+        Regex::new(r#"\\(?:(['"`^~=.])|([cvuH])\b)\s*(?:\{\s*\\?([A-Za-z])\s*\}|\\?([A-Za-z]))"#).unwrap()
+    });
+    let letters = LETTERS.get_or_init(|| Regex::new(r"\\(ss|ae|AE|oe|OE|aa|AA|o|O|l|L|i|TeX|LaTeX)\b\s*").unwrap());
+    let commands = COMMANDS.get_or_init(|| Regex::new(r"\\[A-Za-z]+\*?\s*").unwrap());
+    let mut text = accents
+        .replace_all(&unwrap_verb(s), |cap: &regex::Captures| {
+            let mark = match cap.get(1).or(cap.get(2)).map_or("", |m| m.as_str()) {
+                "'" => '\u{301}',
+                "`" => '\u{300}',
+                "^" => '\u{302}',
+                "\"" => '\u{308}',
+                "~" => '\u{303}',
+                "=" => '\u{304}',
+                "." => '\u{307}',
+                "c" => '\u{327}',
+                "v" => '\u{30c}',
+                "u" => '\u{306}',
+                _ => '\u{30b}',
+            };
+            let letter = cap.get(3).or(cap.get(4)).map_or("", |m| m.as_str());
+            let letter = if letter == "i" { "ı" } else { letter };
+            format!("{}{}", letter, mark)
+        })
+        .into_owned();
+    for (from, to) in [("\\&", "&"), ("\\%", "%"), ("\\$", "$"), ("\\_", "_"), ("\\#", "#")] {
+        text = text.replace(from, to);
+    }
+    text = letters
+        .replace_all(&text, |cap: &regex::Captures| match &cap[1] {
+            "ss" => "ß",
+            "ae" => "æ",
+            "AE" => "Æ",
+            "oe" => "œ",
+            "OE" => "Œ",
+            "aa" => "å",
+            "AA" => "Å",
+            "o" => "ø",
+            "O" => "Ø",
+            "l" => "ł",
+            "L" => "Ł",
+            "i" => "ı",
+            "TeX" => "TeX",
+            _ => "LaTeX",
+        })
+        .into_owned();
+    text = commands.replace_all(&text, "").into_owned();
+    text = text
+        .replace("---", "—")
+        .replace("--", "–")
+        .replace("``", "“")
+        .replace("''", "”")
+        .replace('~', " ")
+        .replace(['{', '}'], "");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// ─────────────────────────────────────────────
+//  CSL-JSON
+// ─────────────────────────────────────────────
+
+fn csl_string(item: &Value, key: &str) -> Option<String> {
+    match item.get(key)? {
+        Value::String(s) => non_empty(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Items of a CSL-JSON file (an array of items, as exported by Zotero).
+fn parse_csl_json(src: &str) -> Result<Vec<Reference>, String> {
+    let items: Vec<Value> = serde_json::from_str(src).map_err(|e| e.to_string())?;
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let key = csl_string(item, "id")?;
+            let names = item.get("author").or_else(|| item.get("editor"));
+            let authors = names
+                .and_then(Value::as_array)
+                .map(|names| {
+                    names
+                        .iter()
+                        .filter_map(|n| match csl_string(n, "literal") {
+                            Some(literal) => Some(Name { family: literal, given: String::new() }),
+                            None => Some(Name {
+                                family: csl_string(n, "family")?,
+                                given: csl_string(n, "given").unwrap_or_default(),
+                            }),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            let issued = item.get("issued");
+            let year = issued
+                .and_then(|d| d.get("date-parts")?.get(0)?.get(0).cloned())
+                .map(|y| y.to_string().trim_matches('"').to_string())
+                .or_else(|| issued.and_then(|d| csl_string(d, "raw").or_else(|| csl_string(d, "literal"))));
+            Some(Reference {
+                kind: match csl_string(item, "type").as_deref() {
+                    Some("article-journal" | "article" | "article-magazine" | "article-newspaper") => {
+                        ReferenceKind::Article
+                    }
+                    Some("book") => ReferenceKind::Book,
+                    Some("chapter") => ReferenceKind::Chapter,
+                    Some("paper-conference") => ReferenceKind::Conference,
+                    Some("thesis") => ReferenceKind::Thesis,
+                    Some("report") => ReferenceKind::Report,
+                    Some("webpage" | "post" | "post-weblog") => ReferenceKind::Webpage,
+                    _ => ReferenceKind::Misc,
+                },
+                authors,
+                title: csl_string(item, "title").unwrap_or_default(),
+                container: csl_string(item, "container-title"),
+                year,
+                volume: csl_string(item, "volume"),
+                issue: csl_string(item, "issue"),
+                pages: csl_string(item, "page"),
+                edition: csl_string(item, "edition"),
+                publisher: csl_string(item, "publisher"),
+                place: csl_string(item, "publisher-place"),
+                doi: csl_string(item, "DOI"),
+                url: csl_string(item, "URL"),
+                key,
+            })
+        })
+        .collect())
+}
+
+// ─────────────────────────────────────────────
+//  Reference list entries
+// ─────────────────────────────────────────────
+
+/// Initials of a given name: "John Ronald" → "J. R." (`dots`) or "J R".
+fn initials(given: &str, dots: bool) -> String {
+    given
+        .split([' ', '-', '.'])
+        .filter_map(|part| part.chars().next())
+        .map(|c| if dots { format!("{}.", c) } else { c.to_string() })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn join_with_and(names: &[String], and: &str) -> String {
+    match names {
+        [] => String::new(),
+        [one] => one.clone(),
+        [first @ .., last] => format!("{}{}{}", first.join(", "), and, last),
+    }
+}
+
+fn em(s: &str) -> String {
+    format!("<em>{}</em>", escape_html(s))
+}
+
+/// `pp. 10–20` for a range, `p. 7` for a single page.
+fn page_range(pages: &str) -> String {
+    let pages = pages.replace("--", "–").replace('-', "–");
+    if pages.contains('–') { format!("pp. {}", pages) } else { format!("p. {}", pages) }
+}
+
+fn doi_or_url(r: &Reference) -> Option<String> {
+    match (&r.doi, &r.url) {
+        (Some(doi), _) => Some(format!("https://doi.org/{}", doi.trim_start_matches("https://doi.org/"))),
+        (None, Some(url)) => Some(url.clone()),
+        _ => None,
+    }
+}
+
+fn link(url: &str) -> String {
+    format!(r#"<a href="{0}">{0}</a>"#, escape_html(url))
+}
+
+/// "J. Smith, A. Doe and B. Roe", IEEE-like.
+fn numeric_entry(r: &Reference) -> String {
+    let names: Vec<String> = r
+        .authors
+        .iter()
+        .map(|n| if n.given.is_empty() { n.family.clone() } else { format!("{} {}", initials(&n.given, true), n.family) })
+        .collect();
+    let mut parts = Vec::new();
+    if !names.is_empty() {
+        parts.push(escape_html(&join_with_and(&names, " and ")));
+    }
+    let book_like = matches!(r.kind, ReferenceKind::Book | ReferenceKind::Thesis | ReferenceKind::Report);
+    parts.push(if book_like { em(&r.title) } else { format!("“{}”", escape_html(&r.title)) });
+    if let Some(container) = &r.container {
+        let inside = matches!(r.kind, ReferenceKind::Chapter | ReferenceKind::Conference);
+        parts.push(if inside { format!("in {}", em(container)) } else { em(container) });
+    }
+    if let Some(edition) = &r.edition {
+        parts.push(format!("{} ed.", escape_html(edition)));
+    }
+    if let Some(volume) = &r.volume {
+        parts.push(format!("vol. {}", escape_html(volume)));
+    }
+    if let Some(issue) = &r.issue {
+        parts.push(format!("no. {}", escape_html(issue)));
+    }
+    match (&r.place, &r.publisher) {
+        (Some(place), Some(publisher)) => parts.push(format!("{}: {}", escape_html(place), escape_html(publisher))),
+        (None, Some(publisher)) => parts.push(escape_html(publisher)),
+        _ => {}
+    }
+    if let Some(pages) = &r.pages {
+        parts.push(escape_html(&page_range(pages)));
+    }
+    if let Some(year) = &r.year {
+        parts.push(escape_html(year));
+    }
+    let mut entry = parts.join(", ") + ".";
+    if let Some(url) = doi_or_url(r) {
+        entry.push_str(&format!(" {}", link(&url)));
+    }
+    entry
+}
+
+/// "Smith, J., Doe, A., & Roe, B. (2020a). Title. <em>Journal</em>, 3(2), 10–20.", APA-like.
+fn author_year_entry(r: &Reference, year: &str) -> String {
+    let names: Vec<String> = r
+        .authors
+        .iter()
+        .map(|n| if n.given.is_empty() { n.family.clone() } else { format!("{}, {}", n.family, initials(&n.given, true)) })
+        .collect();
+    let chinese = r.authors.iter().any(|n| is_cjk(&n.family));
+    let mut entry = match names.len() {
+        0 => String::new(),
+        _ if chinese => escape_html(&names.join(", ")) + " ",
+        1 => escape_html(&names[0]) + " ",
+        _ => escape_html(&join_with_and(&names, ", & ")) + " ",
+    };
+    entry.push_str(&format!("({}). ", escape_html(year)));
+    let book_like = matches!(r.kind, ReferenceKind::Book | ReferenceKind::Thesis | ReferenceKind::Report);
+    entry.push_str(&if book_like { em(&r.title) } else { escape_html(&r.title) });
+    if let (true, Some(edition)) = (book_like, &r.edition) {
+        entry.push_str(&format!(" ({} ed.)", escape_html(edition)));
+    }
+    entry.push('.');
+    if let Some(container) = &r.container {
+        let inside = matches!(r.kind, ReferenceKind::Chapter | ReferenceKind::Conference);
+        entry.push_str(&format!(" {}{}", if inside { "In " } else { "" }, em(container)));
+        if let Some(volume) = &r.volume {
+            entry.push_str(&format!(", {}", em(volume)));
+            if let Some(issue) = &r.issue {
+                entry.push_str(&format!("({})", escape_html(issue)));
+            }
+        }
+        if let Some(pages) = &r.pages {
+            let pages = pages.replace("--", "–").replace('-', "–");
+            entry.push_str(&format!(", {}", if inside { page_range(&pages) } else { pages }));
+        }
+        entry.push('.');
+    }
+    if let Some(publisher) = &r.publisher {
+        entry.push_str(&format!(" {}.", escape_html(publisher)));
+    }
+    if let Some(url) = doi_or_url(r) {
+        entry.push_str(&format!(" {}", link(&url)));
+    }
+    entry
+}
+
+/// GB/T 7714-2015 document type code.
+fn gb_type(r: &Reference) -> &'static str {
+    match r.kind {
+        ReferenceKind::Article => "J",
+        ReferenceKind::Book | ReferenceKind::Chapter => "M",
+        ReferenceKind::Conference => "C",
+        ReferenceKind::Thesis => "D",
+        ReferenceKind::Report => "R",
+        ReferenceKind::Webpage => "EB/OL",
+        ReferenceKind::Misc if r.url.is_some() => "EB/OL",
+        ReferenceKind::Misc => "Z",
+    }
+}
+
+/// Page range with the plain hyphen GB/T 7714 uses; [`clean_latex`] has
+/// already turned BibTeX's `--` into an en dash.
+fn gb_pages(pages: &str) -> String {
+    pages.replace("--", "-").replace(['–', '—'], "-")
+}
+
+/// "张三, 李四, SMITH J. 标题[J]. 期刊, 2020, 3(2): 10-20."
+fn gb_entry(r: &Reference) -> String {
+    let chinese = r.authors.iter().any(|n| is_cjk(&n.family));
+    let mut names: Vec<String> = r
+        .authors
+        .iter()
+        .take(3)
+        .map(|n| {
+            if n.given.is_empty() || is_cjk(&n.family) {
+                format!("{}{}", n.family, n.given)
+            } else {
+                format!("{} {}", n.family.to_uppercase(), initials(&n.given, false))
+            }
+        })
+        .collect();
+    if r.authors.len() > 3 {
+        names.push(if chinese { "等".to_string() } else { "et al".to_string() });
+    }
+    let mut entry = String::new();
+    if !names.is_empty() {
+        entry.push_str(&format!("{}. ", escape_html(&names.join(", "))));
+    }
+    entry.push_str(&format!("{}[{}]", escape_html(&r.title), gb_type(r)));
+    let year = r.year.as_deref().map(escape_html);
+    match r.kind {
+        ReferenceKind::Article => {
+            let mut source = r.container.as_deref().map(escape_html).unwrap_or_default();
+            if let Some(year) = &year {
+                source.push_str(&format!(", {}", year));
+            }
+            if let Some(volume) = &r.volume {
+                source.push_str(&format!(", {}", escape_html(volume)));
+            }
+            if let Some(issue) = &r.issue {
+                source.push_str(&format!("({})", escape_html(issue)));
+            }
+            if let Some(pages) = &r.pages {
+                source.push_str(&format!(": {}", escape_html(&gb_pages(pages))));
+            }
+            entry.push_str(&format!(". {}", source.trim_start_matches(", ")));
+        }
+        _ => {
+            if let (ReferenceKind::Chapter | ReferenceKind::Conference, Some(container)) = (r.kind, &r.container) {
+                entry.push_str(&format!("//{}", escape_html(container)));
+            }
+            if let Some(edition) = &r.edition {
+                entry.push_str(&format!(". {}", escape_html(edition)));
+            }
+            let mut imprint = String::new();
+            if let Some(place) = &r.place {
+                imprint.push_str(&format!("{}: ", escape_html(place)));
+            }
+            if let Some(publisher) = &r.publisher {
+                imprint.push_str(&escape_html(publisher));
+            }
+            if let Some(year) = &year {
+                if !imprint.is_empty() {
+                    imprint.push_str(", ");
+                }
+                imprint.push_str(year);
+            }
+            if let Some(pages) = &r.pages {
+                imprint.push_str(&format!(": {}", escape_html(&gb_pages(pages))));
+            }
+            if !imprint.is_empty() {
+                entry.push_str(&format!(". {}", imprint));
+            }
+            if let (None, Some(url)) = (&r.doi, &r.url) {
+                entry.push_str(&format!(". {}", link(url)));
+            }
+        }
+    }
+    entry.push('.');
+    if let Some(doi) = &r.doi {
+        entry.push_str(&format!(" DOI: {}.", escape_html(doi)));
+    }
+    entry
+}
+
+// ─────────────────────────────────────────────
+//  Citations
+// ─────────────────────────────────────────────
+
+/// One `@key` of a citation, with its locator (`p. 5`).
+struct Cite {
+    key: String,
+    locator: Option<String>,
+}
+
+/// `[@a]`, `[@a, p. 5]`, `[@a; @b]`, ...; `None` for other bracketed text and
+/// for cross-references (`[@fig:x]`).
+fn parse_citation(inner: &str) -> Option<Vec<Cite>> {
+    static ITEM: OnceLock<Regex> = OnceLock::new();
+    let item = ITEM.get_or_init(|| Regex::new(r"^\s*@([^\s,;@]+)\s*(?:,\s*(.*?))?\s*$").unwrap());
+    let cites: Vec<Cite> = inner
+        .split(';')
+        .map(|part| {
+            let cap = item.captures(part)?;
+            Some(Cite { key: cap[1].to_string(), locator: cap.get(2).map(|m| m.as_str().to_string()) })
+        })
+        .collect::<Option<_>>()?;
+    let crossref = |key: &str| ["fig:", "tbl:", "eq:"].iter().any(|p| key.starts_with(p));
+    (!cites.iter().any(|c| crossref(&c.key))).then_some(cites)
+}
+
+/// "a", "b", ..., "z", "aa", "ab", ... for the `i`-th work with the same
+/// authors and year.
+fn year_suffix(i: usize) -> String {
+    let letter = |n: usize| char::from(b'a' + (n % 26) as u8);
+    match i / 26 {
+        0 => letter(i).to_string(),
+        n => format!("{}{}", year_suffix(n - 1), letter(i)),
+    }
+}
+
+/// Short author for author-year citations: "Smith", "Smith & Doe",
+/// "Smith et al.", "张三等".
+fn short_authors(r: &Reference) -> String {
+    let chinese = r.authors.iter().any(|n| is_cjk(&n.family));
+    match r.authors.as_slice() {
+        [] => r.title.clone(),
+        [one] => one.family.clone(),
+        [a, b] if chinese => format!("{}, {}", a.family, b.family),
+        [a, b] => format!("{} & {}", a.family, b.family),
+        [a, ..] if chinese => format!("{}等", a.family),
+        [a, ..] => format!("{} et al.", a.family),
+    }
+}
+
+fn cite_link(key: &str, text: &str) -> String {
+    format!(r##"<a class="citation" href="#ref-{}">{}</a>"##, escape_html(key), escape_html(text))
+}
+
+/// Numbers of a numeric citation, ascending, with runs of three or more
+/// collapsed into a range ("1–3, 5").
+fn number_list(cites: &[(&str, usize)], dash: &str) -> String {
+    let mut sorted = cites.to_vec();
+    sorted.sort_by_key(|(_, n)| *n);
+    sorted.dedup_by_key(|(_, n)| *n);
+    let mut out: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < sorted.len() {
+        let mut j = i;
+        while j + 1 < sorted.len() && sorted[j + 1].1 == sorted[j].1 + 1 {
+            j += 1;
+        }
+        if j - i >= 2 {
+            out.push(format!(
+                "{}{}{}",
+                cite_link(sorted[i].0, &sorted[i].1.to_string()),
+                dash,
+                cite_link(sorted[j].0, &sorted[j].1.to_string())
+            ));
+        } else {
+            out.extend(sorted[i..=j].iter().map(|(key, n)| cite_link(key, &n.to_string())));
+        }
+        i = j + 1;
+    }
+    out.join(", ")
+}
+
+/// Replace the `[@key]` citations in `text` (markdown outside code) and add a
+/// "References" / "参考文献" section at the end listing the cited entries of
+/// `refs` in `style`.  Unknown keys are reported and shown as `[key?]`.
+pub(crate) fn cite(text: &str, refs: &[Reference], style: CitationStyle, chinese: bool) -> String {
+    let by_key: HashMap<&str, &Reference> = refs.iter().map(|r| (r.key.as_str(), r)).collect();
+    let bracket = Regex::new(r"\[([^\[\]]*@[^\[\]]*)\]").unwrap();
+    let code = find_code_regions(text);
+
+    // Citations in document order; links (`[@x](url)`) are not citations.
+    let found: Vec<(std::ops::Range<usize>, Vec<Cite>)> = bracket
+        .captures_iter(text)
+        .filter_map(|cap| {
+            let m = cap.get(0)?;
+            if code.iter().any(|r| r.contains(&m.start())) || text[m.end()..].starts_with(['(', '[', ':']) {
+                return None;
+            }
+            Some((m.range(), parse_citation(&cap[1])?))
+        })
+        .collect();
+    if found.is_empty() {
+        return text.to_string();
+    }
+
+    let mut cited: Vec<&Reference> = Vec::new();
+    let mut reported = HashSet::new();
+    for cite in found.iter().flat_map(|(_, cites)| cites) {
+        match by_key.get(cite.key.as_str()) {
+            Some(r) if !cited.iter().any(|c| c.key == r.key) => cited.push(r),
+            Some(_) => {}
+            None if reported.insert(cite.key.clone()) => {
+                eprintln!("Warning: citation key not found in the bibliography: {}", cite.key)
+            }
+            None => {}
+        }
+    }
+
+    // Author-year: sorted by author and year, "2020a" / "2020b" for works
+    // with the same authors and year.
+    let mut years: HashMap<&str, String> = HashMap::new();
+    if style == CitationStyle::AuthorYear {
+        cited.sort_by_cached_key(|r| (short_authors(r), r.year.clone(), r.title.clone()));
+        let mut seen: HashMap<(String, String), Vec<&str>> = HashMap::new();
+        for r in &cited {
+            let year = r.year.clone().unwrap_or_else(|| "n.d.".to_string());
+            seen.entry((short_authors(r), year)).or_default().push(&r.key);
+        }
+        for ((_, year), keys) in seen {
+            for (i, key) in keys.iter().enumerate() {
+                let suffix = if keys.len() > 1 { year_suffix(i) } else { String::new() };
+                years.insert(key, format!("{}{}", year, suffix));
+            }
+        }
+    }
+    let numbers: HashMap<&str, usize> = cited.iter().enumerate().map(|(i, r)| (r.key.as_str(), i + 1)).collect();
+
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, cites) in &found {
+        out.push_str(&text[last..range.start]);
+        last = range.end;
+        let missing: Vec<&Cite> = cites.iter().filter(|c| !numbers.contains_key(c.key.as_str())).collect();
+        if !missing.is_empty() {
+            let keys: Vec<String> = missing.iter().map(|c| format!("{}?", c.key)).collect();
+            out.push_str(&format!("[{}]", escape_html(&keys.join("; "))));
+            continue;
+        }
+        let locator = |c: &Cite| c.locator.as_ref().map(|l| format!(", {}", escape_html(l))).unwrap_or_default();
+        match style {
+            CitationStyle::AuthorYear => {
+                let items: Vec<String> = cites
+                    .iter()
+                    .map(|c| {
+                        let r = by_key[c.key.as_str()];
+                        let label = format!("{}, {}", short_authors(r), years[c.key.as_str()]);
+                        cite_link(&c.key, &label) + &locator(c)
+                    })
+                    .collect();
+                out.push_str(&format!("({})", items.join("; ")));
+            }
+            CitationStyle::Numeric | CitationStyle::GbT7714 => {
+                let gb = style == CitationStyle::GbT7714;
+                let inner = if cites.iter().any(|c| c.locator.is_some()) && !(gb && cites.len() == 1) {
+                    cites
+                        .iter()
+                        .map(|c| cite_link(&c.key, &numbers[c.key.as_str()].to_string()) + &locator(c))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                } else {
+                    let list: Vec<(&str, usize)> =
+                        cites.iter().map(|c| (c.key.as_str(), numbers[c.key.as_str()])).collect();
+                    number_list(&list, if gb { "-" } else { "–" })
+                };
+                if gb {
+                    // GB/T 7714 puts a page locator after the bracket: [3]25.
+                    let page = cites[0].locator.as_deref().filter(|_| cites.len() == 1).map(escape_html);
+                    out.push_str(&format!(
+                        r#"<sup class="citation-group">[{}]{}</sup>"#,
+                        inner,
+                        page.unwrap_or_default()
+                    ));
+                } else {
+                    out.push_str(&format!("[{}]", inner));
+                }
+            }
+        }
+    }
+    out.push_str(&text[last..]);
+
+    if cited.is_empty() {
+        return out;
+    }
+    let entries: String = cited
+        .iter()
+        .map(|r| {
+            let key = r.key.as_str();
+            let (label, entry) = match style {
+                CitationStyle::Numeric => (format!("[{}]", numbers[key]), numeric_entry(r)),
+                CitationStyle::GbT7714 => (format!("[{}]", numbers[key]), gb_entry(r)),
+                CitationStyle::AuthorYear => (String::new(), author_year_entry(r, &years[key])),
+            };
+            let label = if label.is_empty() {
+                String::new()
+            } else {
+                format!(r#"<span class="reference-label">{}</span> "#, label)
+            };
+            format!("<div class=\"reference\" id=\"ref-{}\">{}{}</div>\n", escape_html(key), label, entry)
+        })
+        .collect();
+    let class = match style {
+        CitationStyle::Numeric => "numeric",
+        CitationStyle::AuthorYear => "author-year",
+        CitationStyle::GbT7714 => "gb7714",
+    };
+    let level = section_level(text).unwrap_or(1);
+    format!(
+        "{}\n\n{} {} {{#references}}\n\n<div class=\"references references-{}\">\n{}</div>\n",
+        out.trim_end(),
+        "#".repeat(level),
+        if chinese { "参考文献" } else { "References" },
+        class,
+        entries
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIB: &str = r#"
+@string{pub = "Addison-Wesley"}
+@comment{ignored}
+@book{knuth84,
+  author    = {Knuth, Donald E.},
+  title     = {The {\TeX}book},
+  publisher = pub # " Professional",
+  address   = {Reading, MA},
+  year      = 1984,
+}
+@article(smith20,
+  author  = "John Smith and Doe, Jane and {World Health Organization}",
+  title   = {Caf\'{e} culture and G\"odel's \emph{theorem}},
+  journal = {Journal of Things},
+  volume  = {3}, number = {2}, pages = {10--20},
+  year    = {2020},
+  doi     = {10.1000/x\_y},
+  url     = {https://example.org/~smith/a--b},
+)
+"#;
+
+    fn refs() -> Vec<Reference> {
+        parse_bibtex(BIB).unwrap()
+    }
+
+    #[test]
+    fn parses_bibtex_entries_strings_and_names() {
+        let refs = refs();
+        assert_eq!(refs.len(), 2);
+        let book = &refs[0];
+        assert_eq!(book.key, "knuth84");
+        assert_eq!(book.kind, ReferenceKind::Book);
+        assert_eq!(book.title, "The TeXbook");
+        assert_eq!(book.publisher.as_deref(), Some("Addison-Wesley Professional"));
+        assert_eq!(book.year.as_deref(), Some("1984"));
+        assert_eq!(book.authors, vec![Name { family: "Knuth".into(), given: "Donald E.".into() }]);
+
+        let article = &refs[1];
+        assert_eq!(article.kind, ReferenceKind::Article);
+        assert_eq!(article.title, "Cafe\u{301} culture and Go\u{308}del's theorem");
+        assert_eq!(article.pages.as_deref(), Some("10–20"));
+        let families: Vec<&str> = article.authors.iter().map(|n| n.family.as_str()).collect();
+        assert_eq!(families, ["Smith", "Doe", "World Health Organization"]);
+        assert_eq!(article.authors[1].given, "Jane");
+        assert_eq!(article.authors[2].given, "");
+    }
+
+    #[test]
+    fn reads_url_and_doi_verbatim() {
+        let article = &refs()[1];
+        assert_eq!(article.url.as_deref(), Some("https://example.org/~smith/a--b"));
+        assert_eq!(article.doi.as_deref(), Some("10.1000/x_y"));
+    }
+
+    #[test]
+    fn unclosed_entry_is_an_error() {
+        assert!(parse_bibtex("@book{a, title = {x}").is_err());
+    }
+
+    /// Accents come out as combining marks after their letter.
+    #[test]
+    fn clean_latex_accents_and_letters() {
+        assert_eq!(clean_latex(r"Erd{\H o}s"), "Erdo\u{30b}s");
+        assert_eq!(clean_latex(r"\c{c}a \v{s} \u a \'\i"), "c\u{327}a s\u{30c} a\u{306} ı\u{301}");
+        assert_eq!(clean_latex(r"Stra{\ss}e \o{} \AA"), "Straße ø Å");
+        assert_eq!(clean_latex(r"a\&b 50\% --- ``q''"), "a&b 50% — “q”");
+    }
+
+    #[test]
+    fn clean_latex_keeps_longer_commands_apart_from_accents() {
+        assert_eq!(clean_latex(r"\underline{things}"), "things");
+        assert_eq!(clean_latex(r"\verb|x| and \verb+y z+"), "x and y z");
+        assert_eq!(clean_latex(r"see \cite{k} at \url{a.b}"), "see k at a.b");
+        assert_eq!(clean_latex(r"\Huge{Big} \verbatim"), "Big");
+    }
+
+    #[test]
+    fn parses_csl_json() {
+        let json = r#"[{"id": "li", "type": "article-journal", "title": "标题",
+            "author": [{"family": "李", "given": "四"}, {"literal": "Acme Inc."}],
+            "container-title": "学报", "issued": {"date-parts": [[2019, 5]]},
+            "page": "1-9", "URL": "https://x.cn"}]"#;
+        let refs = parse_csl_json(json).unwrap();
+        assert_eq!(refs.len(), 1);
+        let r = &refs[0];
+        assert_eq!((r.key.as_str(), r.kind), ("li", ReferenceKind::Article));
+        assert_eq!(r.year.as_deref(), Some("2019"));
+        assert_eq!(r.authors[1], Name { family: "Acme Inc.".into(), given: String::new() });
+        assert_eq!(r.url.as_deref(), Some("https://x.cn"));
+        assert!(parse_csl_json("{}").is_err());
+    }
+
+    #[test]
+    fn year_suffixes_continue_past_z() {
+        let suffixes: Vec<String> = [0, 1, 25, 26, 27, 51, 52].into_iter().map(year_suffix).collect();
+        assert_eq!(suffixes, ["a", "b", "z", "aa", "ab", "az", "ba"]);
+    }
+
+    #[test]
+    fn number_list_collapses_runs_of_three() {
+        let cites = [("a", 1), ("b", 2), ("c", 3), ("e", 5), ("f", 6), ("a", 1)];
+        let text = number_list(&cites, "–");
+        let plain = Regex::new(r"<[^>]+>").unwrap().replace_all(&text, "");
+        assert_eq!(plain, "1–3, 5, 6");
+    }
+
+    #[test]
+    fn parses_citations_but_not_cross_references() {
+        let cites = parse_citation("@a, p. 5; @b").unwrap();
+        assert_eq!(cites.len(), 2);
+        assert_eq!(cites[0].key, "a");
+        assert_eq!(cites[0].locator.as_deref(), Some("p. 5"));
+        assert_eq!(cites[1].locator, None);
+        assert!(parse_citation("@fig:plot").is_none());
+        assert!(parse_citation("see @a").is_none());
+    }
+
+    #[test]
+    fn gb7714_entry_uses_plain_hyphens_and_uppercase_names() {
+        let entry = gb_entry(&refs()[1]);
+        assert!(entry.starts_with("SMITH J, DOE J, World Health Organization. "), "{}", entry);
+        assert!(entry.contains("[J]. Journal of Things, 2020, 3(2): 10-20."), "{}", entry);
+        assert!(entry.ends_with(" DOI: 10.1000/x_y."), "{}", entry);
+    }
+
+    #[test]
+    fn cite_numeric_adds_reference_list() {
+        let text = "# Intro\n\nA [@smith20] and [@knuth84; @smith20] and `[@code]` and [@nope].\n";
+        let out = cite(text, &refs(), CitationStyle::Numeric, false);
+        assert!(out.contains(r##"A [<a class="citation" href="#ref-smith20">1</a>]"##), "{}", out);
+        assert!(out.contains("`[@code]`"));
+        assert!(out.contains("[nope?]"));
+        assert!(out.contains("# References {#references}"));
+        assert!(out.contains(r#"<a href="https://doi.org/10.1000/x_y">"#));
+        let smith = out.find(r#"id="ref-smith20""#).unwrap();
+        let knuth = out.find(r#"id="ref-knuth84""#).unwrap();
+        assert!(smith < knuth);
+    }
+
+    #[test]
+    fn cite_author_year_suffixes_same_author_and_year() {
+        let mut refs = refs();
+        let mut again = refs[0].clone();
+        again.key = "knuth84b".into();
+        again.title = "Another".into();
+        refs.push(again);
+        let out = cite("[@knuth84] [@knuth84b]", &refs, CitationStyle::AuthorYear, false);
+        assert!(out.contains(">Knuth, 1984b</a>)"), "{}", out);
+        assert!(out.contains(">Knuth, 1984a</a>)"), "{}", out);
+    }
+}
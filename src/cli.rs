@@ -1,4 +1,4 @@
-/// cli.rs — Command-line argument definitions.  Mirrors cli.js.
+//! cli.rs — Command-line argument definitions.  Mirrors cli.js.
 
 use clap::Parser as ClapParser;
 use std::path::PathBuf;
@@ -10,14 +10,32 @@ use std::path::PathBuf;
     version = "1.0.0"
 )]
 pub struct Args {
-    /// Markdown 输入文件路径
+    /// Markdown 输入: 文件、目录或 glob 模式 (如 "docs/**/*.md")，可指定多个。
+    /// 兼容旧用法: 单个输入后跟一个 .pdf/.html 路径时视为输出文件。`-` 表示从标准输入读取
     #[arg(value_name = "INPUT")]
-    pub input: PathBuf,
+    pub inputs: Vec<PathBuf>,
 
-    /// PDF/HTML 输出文件路径 (可选，默认同目录同名)
-    #[arg(value_name = "OUTPUT")]
+    /// 配置文件路径 (默认在输入所在目录及其上级目录中查找 md2pdf.toml)
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// 使用配置文件中的 [profile.<NAME>] 配置组
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// 输出路径: 单个文件时为输出文件 (默认同目录同名)，批量转换时为输出目录 (保持源目录结构)。
+    /// `-` 表示写到标准输出 (从标准输入读取时的默认值)
+    #[arg(short, long, value_name = "OUTPUT")]
     pub output: Option<PathBuf>,
 
+    /// 监视模式: 输入文件、引用的本地图片或资源目录变化时自动重新转换
+    #[arg(short, long)]
+    pub watch: bool,
+
+    /// 批量转换时的并发数
+    #[arg(short, long, default_value_t = 4)]
+    pub jobs: usize,
+
     /// 显示详细信息
     #[arg(short, long)]
     pub verbose: bool,
@@ -26,37 +44,173 @@ pub struct Args {
     #[arg(short, long, default_value = "pdf")]
     pub format: String,
 
-    /// 页边距, 例如 20mm (默认: 0mm)
+    /// 页边距, 例如 20mm (默认: 0mm)。支持 CSS 简写: "20mm 15mm" (上下 左右) 或 "上 右 下 左"
     #[arg(long, default_value = "0mm")]
     pub margin: String,
 
+    /// 上边距 (覆盖 --margin)
+    #[arg(long, value_name = "LENGTH")]
+    pub margin_top: Option<String>,
+
+    /// 右边距 (覆盖 --margin)
+    #[arg(long, value_name = "LENGTH")]
+    pub margin_right: Option<String>,
+
+    /// 下边距 (覆盖 --margin)
+    #[arg(long, value_name = "LENGTH")]
+    pub margin_bottom: Option<String>,
+
+    /// 左边距 (覆盖 --margin)
+    #[arg(long, value_name = "LENGTH")]
+    pub margin_left: Option<String>,
+
+    /// 纸张大小 (A3|A4|A5|B5|Letter|Legal 或自定义 宽x高, 如 210x297mm、8.5inx11in)
+    #[arg(long, default_value = "A4")]
+    pub paper: String,
+
     /// 横向页面
     #[arg(long)]
     pub landscape: bool,
 
-    /// 字体大小 (small|medium|large|xlarge 或具体数值如 14px)
-    #[arg(long, default_value = "medium")]
-    pub font_size: String,
+    /// 页眉: 预设 (page|page-right|page-of-pages|page-of-pages-zh|title-page|title-date|section-page|none)、
+    /// HTML 模板文件或内联模板。占位符: {page} {pages} {title} {date} {section}
+    #[arg(long, value_name = "TEMPLATE")]
+    pub header: Option<String>,
+
+    /// 页脚 (取值同 --header)，例如 --footer page-of-pages
+    #[arg(long, value_name = "TEMPLATE")]
+    pub footer: Option<String>,
+
+    /// 封面页: 内置版式 (classic|modern|formal|none) 或封面 HTML 模板文件，只写 --cover 即 classic。
+    /// 模板占位符: {title} {subtitle} {authors} {organization} {date} {logo} {version}
+    #[arg(long, value_name = "LAYOUT", num_args = 0..=1, require_equals = true, default_missing_value = "classic")]
+    pub cover: Option<String>,
+
+    /// 文档标题 (front matter 中的 title 优先，默认为文件名；从标准输入读取时默认为 "Markdown to PDF")
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// 副标题 (用于封面)
+    #[arg(long)]
+    pub subtitle: Option<String>,
+
+    /// 作者 (可指定多次，front matter 中的 author 优先)
+    #[arg(long = "author", value_name = "NAME")]
+    pub authors: Vec<String>,
+
+    /// 单位/组织 (用于封面)
+    #[arg(long)]
+    pub organization: Option<String>,
+
+    /// 日期 (front matter 中的 date 优先)
+    #[arg(long)]
+    pub date: Option<String>,
+
+    /// 文档主题/摘要，写入 PDF 元数据 (front matter 中的 subject 或 description 优先)
+    #[arg(long)]
+    pub subject: Option<String>,
+
+    /// 关键词，逗号分隔，写入 PDF 元数据 (front matter 中的 keywords 优先)
+    #[arg(long, value_delimiter = ',', value_name = "WORDS")]
+    pub keywords: Vec<String>,
+
+    /// 封面 Logo: 图片路径或 URL
+    #[arg(long, value_name = "IMAGE")]
+    pub logo: Option<String>,
+
+    /// 文档版本号 (用于封面)
+    #[arg(long, value_name = "VERSION")]
+    pub doc_version: Option<String>,
+
+    /// 将本地图片以 base64 data URI 内嵌，生成单文件 HTML
+    #[arg(long)]
+    pub embed_images: bool,
+
+    /// 内嵌图片的大小上限 (如 500KB、10MB)，更大的图片保留原链接
+    #[arg(long, default_value = "10MB", value_name = "SIZE")]
+    pub embed_max_size: String,
+
+    /// 在文档开头插入目录 (文档中的 [TOC] 行总会被替换为目录)
+    #[arg(long)]
+    pub toc: bool,
+
+    /// 在目录之后插入插图目录 (文档中的 [LOF] 行总会被替换为插图目录)
+    #[arg(long)]
+    pub list_of_figures: bool,
+
+    /// 插入表格目录 (文档中的 [LOT] 行总会被替换为表格目录)
+    #[arg(long)]
+    pub list_of_tables: bool,
+
+    /// 文档语言 (如 zh-CN、en)，决定目录标题、图表编号前缀等生成文字 (front matter 中的 lang 优先，默认按内容判断)
+    #[arg(long)]
+    pub lang: Option<String>,
+
+    /// 不生成 PDF 书签 (默认按 h1–h6 标题生成书签目录)
+    #[arg(long)]
+    pub no_outline: bool,
+
+    /// 主题: 内置主题 (default|github|academic|minimal|gongwen) 或主题目录
+    /// (其中的 *.css 文件，以及目录或 fonts/ 子目录中的字体文件)
+    #[arg(long, default_value = "default", value_name = "THEME")]
+    pub theme: String,
+
+    /// 追加的 CSS 文件 (在生成的样式之后加载，可指定多次)
+    #[arg(long, value_name = "FILE")]
+    pub css: Vec<PathBuf>,
+
+    /// HTML 模板文件，替换内置的文档骨架。占位符: {title} {lang} {meta} {styles} {scripts} {content}
+    #[arg(long, value_name = "FILE")]
+    pub template: Option<PathBuf>,
 
-    /// 中文字体 (simsun|simhei|simkai|fangsong|yahei|auto)
-    #[arg(long, default_value = "simsun")]
-    pub chinese_font: String,
+    /// 字体大小 (small|medium|large|xlarge 或具体数值如 14px)，默认由主题决定
+    #[arg(long)]
+    pub font_size: Option<String>,
+
+    /// 中文字体 (simsun|simhei|simkai|fangsong|yahei|auto)，默认由主题决定
+    #[arg(long)]
+    pub chinese_font: Option<String>,
+
+    /// 文字厚度 (light|normal|medium|semibold|bold|black 或数值如 400)，默认由主题决定
+    #[arg(long)]
+    pub font_weight: Option<String>,
+
+    /// 行间距 (tight|normal|loose|relaxed 或数值如 1.6)，默认由主题决定
+    #[arg(long)]
+    pub line_spacing: Option<String>,
+
+    /// 段落间距 (tight|normal|loose|relaxed 或数值如 1em)，默认由主题决定
+    #[arg(long)]
+    pub paragraph_spacing: Option<String>,
+
+    /// 数学公式间距 (tight|normal|loose|relaxed 或数值如 20px)，默认由主题决定
+    #[arg(long)]
+    pub math_spacing: Option<String>,
+
+    /// 代码高亮主题 (github|one-light|one-dark|solarized-light|solarized-dark|
+    /// gruvbox-light|gruvbox-dark|monokai|dracula|nord|none)
+    #[arg(long, default_value = "github")]
+    pub code_theme: String,
+
+    /// 代码块显示行号
+    #[arg(long)]
+    pub line_numbers: bool,
 
-    /// 文字厚度 (light|normal|medium|semibold|bold|black 或数值如 400)
-    #[arg(long, default_value = "medium")]
-    pub font_weight: String,
+    /// 数学公式渲染位置 (client: 浏览器端 KaTeX | server: 生成时静态渲染, HTML 无需脚本)
+    #[arg(long, default_value = "client")]
+    pub math_render: String,
 
-    /// 行间距 (tight|normal|loose|relaxed 或数值如 1.6)
-    #[arg(long, default_value = "normal")]
-    pub line_spacing: String,
+    /// 公式编号 (labeled: 仅带 \label 的公式 | all: 全部行间公式 | section: 按章节编号，如 (2.1))
+    #[arg(long, default_value = "labeled", value_name = "MODE")]
+    pub equation_numbers: String,
 
-    /// 段落间距 (tight|normal|loose|relaxed 或数值如 1em)
-    #[arg(long, default_value = "tight")]
-    pub paragraph_spacing: String,
+    /// 参考文献文件 (BibTeX .bib 或 CSL-JSON .json，可指定多次)，文中 [@key] 引用其中的条目
+    #[arg(long, value_name = "FILE")]
+    pub bibliography: Vec<PathBuf>,
 
-    /// 数学公式间距 (tight|normal|loose|relaxed 或数值如 20px)
-    #[arg(long, default_value = "tight")]
-    pub math_spacing: String,
+    /// 引用格式 (numeric: [1] | author-year: (Smith, 2020) | gb7714: GB/T 7714 上标 [1])
+    #[arg(long, default_value = "numeric", value_name = "STYLE")]
+    pub citation_style: String,
 
     /// Chrome 可执行文件路径 (可选，留空则自动搜索)
     #[arg(long)]
@@ -1,4 +1,7 @@
-/// config.rs — constants / defaults  (mirrors config.js)
+//! config.rs — constants / defaults  (mirrors config.js)
+
+use regex::Regex;
+use std::path::PathBuf;
 
 // ─────────────────────────────────────────────
 //  Font / spacing presets
@@ -83,34 +86,189 @@ pub fn normalize_with_unit(value: &str, unit: &str) -> String {
     }
 }
 
+/// Units accepted in lengths, with how many of them make one inch.
+const LENGTH_UNITS: [(&str, f64); 5] = [
+    ("mm", 25.4),
+    ("cm", 2.54),
+    ("in", 1.0),
+    ("px", 96.0),
+    ("pt", 72.0),
+];
+
+/// Parse a CSS length (e.g. "20mm", "1in", "0.5cm", "96px", "72pt") to inches.
+/// A bare number is taken as millimetres.
+pub fn length_to_inches(s: &str) -> Option<f64> {
+    let s = s.trim();
+    for (unit, per_inch) in LENGTH_UNITS {
+        if let Some(v) = s.strip_suffix(unit) {
+            return v.trim().parse::<f64>().ok().map(|v| v / per_inch);
+        }
+    }
+    s.parse::<f64>().ok().map(|v| v / 25.4)
+}
+
 /// Parse a CSS margin string (e.g. "20mm", "1in", "0.5cm") to inches.
+/// Unparseable values fall back to 20mm.
 pub fn margin_to_inches(s: &str) -> f64 {
-    let s = s.trim();
-    if let Some(v) = s.strip_suffix("mm") {
-        v.trim().parse::<f64>().unwrap_or(20.0) / 25.4
-    } else if let Some(v) = s.strip_suffix("cm") {
-        v.trim().parse::<f64>().unwrap_or(2.0) / 2.54
-    } else if let Some(v) = s.strip_suffix("in") {
-        v.trim().parse::<f64>().unwrap_or(0.787)
-    } else if let Some(v) = s.strip_suffix("px") {
-        v.trim().parse::<f64>().unwrap_or(0.0) / 96.0
-    } else {
-        s.parse::<f64>().unwrap_or(20.0) / 25.4
+    length_to_inches(s).unwrap_or(20.0 / 25.4)
+}
+
+/// Parse a byte size such as "500KB", "10MB", "1.5 MiB" or a bare number of
+/// bytes.  K/M/G are binary multiples.
+pub fn byte_size(s: &str) -> Option<u64> {
+    let s = s.trim().to_ascii_lowercase();
+    let number_end = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let value: f64 = s[..number_end].parse().ok()?;
+    let multiplier = match s[number_end..].trim().trim_end_matches(['b', 'i']) {
+        ""  => 1u64,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        _ => return None,
+    };
+    Some((value * multiplier as f64) as u64)
+}
+
+/// Per-side page margins in inches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Margins {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Margins {
+    pub fn uniform(inches: f64) -> Self {
+        Self { top: inches, right: inches, bottom: inches, left: inches }
+    }
+
+    /// Parse CSS margin shorthand with 1–4 lengths: "20mm", "20mm 15mm",
+    /// "20mm 15mm 25mm", "20mm 15mm 25mm 10mm" (top right bottom left).
+    pub fn parse(s: &str) -> Option<Self> {
+        let v: Vec<f64> = s
+            .split_whitespace()
+            .map(length_to_inches)
+            .collect::<Option<_>>()?;
+        match v[..] {
+            [all]             => Some(Self::uniform(all)),
+            [tb, lr]          => Some(Self { top: tb, right: lr, bottom: tb, left: lr }),
+            [t, lr, b]        => Some(Self { top: t, right: lr, bottom: b, left: lr }),
+            [t, r, b, l]      => Some(Self { top: t, right: r, bottom: b, left: l }),
+            _ => None,
+        }
     }
 }
 
+/// Paper presets → portrait (width, height) in inches.
+fn paper_preset(name: &str) -> Option<(f64, f64)> {
+    let mm = |w: f64, h: f64| Some((w / 25.4, h / 25.4));
+    match name.to_ascii_lowercase().as_str() {
+        "a3"     => mm(297.0, 420.0),
+        "a4"     => mm(210.0, 297.0),
+        "a5"     => mm(148.0, 210.0),
+        "b5"     => mm(176.0, 250.0),
+        "letter" => Some((8.5, 11.0)),
+        "legal"  => Some((8.5, 14.0)),
+        _ => None,
+    }
+}
+
+/// Parse `--paper`: a preset (A3, A4, A5, B5, Letter, Legal) or a custom
+/// `WxH` size such as "210x297mm", "8.5in x 11in" or "180mm×240mm".
+/// Returns portrait (width, height) in inches.
+pub fn paper_size(s: &str) -> Option<(f64, f64)> {
+    if let Some(size) = paper_preset(s.trim()) {
+        return Some(size);
+    }
+    let re = Regex::new(r"^\s*([\d.]+)\s*([a-z]*)\s*[x×*]\s*([\d.]+)\s*([a-z]*)\s*$").ok()?;
+    let lower = s.to_ascii_lowercase();
+    let cap = re.captures(&lower)?;
+    // "210x297mm": a unit given only once applies to both sides.
+    let unit_w = if cap[2].is_empty() { &cap[4] } else { &cap[2] };
+    let unit_h = if cap[4].is_empty() { &cap[2] } else { &cap[4] };
+    let w = length_to_inches(&format!("{}{}", &cap[1], unit_w))?;
+    let h = length_to_inches(&format!("{}{}", &cap[3], unit_h))?;
+    (w > 0.0 && h > 0.0).then_some((w, h))
+}
+
+// ─────────────────────────────────────────────
+//  Page headers / footers
+// ─────────────────────────────────────────────
+
+/// Header / footer presets → templates.  Placeholders: `{page}`, `{pages}`,
+/// `{title}`, `{date}`, `{section}`.
+fn header_footer_preset(name: &str) -> Option<&'static str> {
+    let template = match name {
+        "page"            => r#"<div style="text-align: center">{page}</div>"#,
+        "page-right"      => r#"<div style="text-align: right">{page}</div>"#,
+        "page-of-pages"   => r#"<div style="text-align: center">Page {page} of {pages}</div>"#,
+        "page-of-pages-zh"=> r#"<div style="text-align: center">第 {page} 页 / 共 {pages} 页</div>"#,
+        "title-page"      => r#"<div style="display: flex; justify-content: space-between"><span>{title}</span><span>{page}</span></div>"#,
+        "title-date"      => r#"<div style="display: flex; justify-content: space-between"><span>{title}</span><span>{date}</span></div>"#,
+        "section-page"    => r#"<div style="display: flex; justify-content: space-between"><span>{section}</span><span>{page} / {pages}</span></div>"#,
+        _ => return None,
+    };
+    Some(template)
+}
+
+/// Resolve a `--header` / `--footer` value: a preset name (page, page-right,
+/// page-of-pages, page-of-pages-zh, title-page, title-date, section-page),
+/// the path of an HTML file, or an inline template.  `none` turns it off.
+pub fn header_footer_template(value: &str) -> std::io::Result<Option<String>> {
+    let value = value.trim();
+    if value.is_empty() || value == "none" {
+        return Ok(None);
+    }
+    if let Some(template) = header_footer_preset(value) {
+        return Ok(Some(template.to_string()));
+    }
+    if std::path::Path::new(value).is_file() {
+        return std::fs::read_to_string(value).map(Some);
+    }
+    Ok(Some(value.to_string()))
+}
+
 // ─────────────────────────────────────────────
 //  StyleOptions
 // ─────────────────────────────────────────────
 
+/// Document styling.  The theme supplies the stylesheet; each option that is
+/// set overrides the theme's value for that property.
 #[derive(Debug, Clone)]
 pub struct StyleOptions {
-    pub font_size: String,
-    pub chinese_font: String,
-    pub font_weight: String,
-    pub line_spacing: String,
-    pub paragraph_spacing: String,
-    pub math_spacing: String,
+    /// Built-in theme name or theme directory (see [`crate::theme::load_theme`]).
+    pub theme: String,
+    pub font_size: Option<String>,
+    pub chinese_font: Option<String>,
+    pub font_weight: Option<String>,
+    pub line_spacing: Option<String>,
+    pub paragraph_spacing: Option<String>,
+    pub math_spacing: Option<String>,
+    /// Syntax highlighting theme (see [`crate::highlight::theme_names`]).
+    pub code_theme: String,
+    /// User stylesheets appended after the generated CSS, in order.
+    pub css_files: Vec<PathBuf>,
+    /// User HTML template replacing the built-in document skeleton (see
+    /// [`crate::template::generate_html_document`]).
+    pub html_template: Option<PathBuf>,
+}
+
+impl Default for StyleOptions {
+    fn default() -> Self {
+        Self {
+            theme:             crate::theme::DEFAULT_THEME.into(),
+            font_size:         None,
+            chinese_font:      None,
+            font_weight:       None,
+            line_spacing:      None,
+            paragraph_spacing: None,
+            math_spacing:      None,
+            code_theme:        "github".into(),
+            css_files:         Vec::new(),
+            html_template:     None,
+        }
+    }
 }
 
 // ─────────────────────────────────────────────
@@ -119,19 +277,225 @@ pub struct StyleOptions {
 
 #[derive(Debug, Clone)]
 pub struct PdfOptions {
-    pub margin_inches: f64,
+    /// Portrait paper size in inches; `landscape` rotates it.
+    pub paper_width: f64,
+    pub paper_height: f64,
+    pub margins: Margins,
     pub landscape: bool,
+    /// Header / footer templates (see [`header_footer_template`]); printed in
+    /// the top / bottom margin, which must be large enough to hold them.
+    pub header_template: Option<String>,
+    pub footer_template: Option<String>,
+    /// Add a bookmark outline built from the h1–h6 headings.
+    pub outline: bool,
+    /// Document subject and keywords for the PDF metadata, used when the
+    /// front matter has none.
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
 }
 
 impl Default for PdfOptions {
     fn default() -> Self {
         Self {
-            margin_inches: 0.787, // 20mm ≈ 0.787 inches  (PDF_CONFIG default)
+            paper_width: 8.27,   // A4
+            paper_height: 11.69,
+            margins: Margins::uniform(0.787), // 20mm ≈ 0.787 inches  (PDF_CONFIG default)
             landscape: false,
+            header_template: None,
+            footer_template: None,
+            outline: true,
+            subject: None,
+            keywords: Vec::new(),
+        }
+    }
+}
+
+// ─────────────────────────────────────────────
+//  OutputFormat
+// ─────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Pdf,
+    Html,
+}
+
+impl OutputFormat {
+    /// Parse the `--format` value (`pdf|html`).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pdf"  => Some(Self::Pdf),
+            "html" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    /// File extension of the generated document.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Pdf  => "pdf",
+            Self::Html => "html",
         }
     }
 }
 
+// ─────────────────────────────────────────────
+//  RenderOptions
+// ─────────────────────────────────────────────
+
+/// Where LaTeX math is typeset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MathRender {
+    /// Emit `$...$` and let KaTeX auto-render typeset it in the browser.
+    #[default]
+    Client,
+    /// Typeset with KaTeX during `render()`; the HTML needs no JavaScript.
+    Server,
+}
+
+impl MathRender {
+    /// Parse the `--math-render` value (`client|server`).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "client" => Some(Self::Client),
+            "server" => Some(Self::Server),
+            _ => None,
+        }
+    }
+}
+
+/// Which display equations get a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EquationNumbering {
+    /// Only equations with a `\label{}`, so they can be referenced.
+    #[default]
+    Labeled,
+    /// Every display equation: (1), (2), ...
+    All,
+    /// Every display equation, counted within its section: (2.1), (2.2), ...;
+    /// equations before the first section are numbered (1), (2), ...
+    Section,
+}
+
+impl EquationNumbering {
+    /// Parse the `--equation-numbers` value (`labeled|all|section`).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "labeled" | "labelled" => Some(Self::Labeled),
+            "all" => Some(Self::All),
+            "section" => Some(Self::Section),
+            _ => None,
+        }
+    }
+}
+
+/// How citations and the reference list are formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CitationStyle {
+    /// `[1]`, references numbered in order of first citation.
+    #[default]
+    Numeric,
+    /// `(Smith et al., 2020)`, references sorted by author and year.
+    AuthorYear,
+    /// GB/T 7714-2015 numeric (顺序编码制): superscript `[1]`, typed entries
+    /// such as `[J]` and `[M]`.
+    GbT7714,
+}
+
+impl CitationStyle {
+    /// Parse the `--citation-style` value (`numeric|author-year|gb7714`).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "numeric" => Some(Self::Numeric),
+            "author-year" | "authoryear" => Some(Self::AuthorYear),
+            "gb7714" | "gbt7714" | "gb/t7714" | "gb-t-7714" => Some(Self::GbT7714),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub math: MathRender,
+    /// Display equations that are numbered; `\notag` / `\nonumber` opt out.
+    pub equation_numbers: EquationNumbering,
+    /// Put a table of contents at the top of documents without a `[TOC]` marker.
+    pub toc: bool,
+    /// Likewise a list of figures (`[LOF]`) and a list of tables (`[LOT]`).
+    pub list_of_figures: bool,
+    pub list_of_tables: bool,
+    /// Document language (front matter `lang`, else `--lang`), for generated
+    /// text such as the table of contents title and caption labels.
+    pub lang: Option<String>,
+    /// Number the lines of code blocks.
+    pub line_numbers: bool,
+    /// Cover page before the content.
+    pub cover: CoverOptions,
+    /// Directory relative image paths are resolved against (the input
+    /// file's directory); the current directory when `None`.
+    pub base_dir: Option<PathBuf>,
+    /// Inline local images as data URIs, making the HTML self-contained.
+    pub embed_images: bool,
+    /// Images larger than this many bytes are linked instead of embedded.
+    pub embed_max_size: u64,
+    /// BibTeX (`.bib`) or CSL-JSON (`.json`) files that `[@key]` citations
+    /// refer to; citations are left alone when empty.
+    pub bibliography: Vec<PathBuf>,
+    pub citation_style: CitationStyle,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            math:             MathRender::default(),
+            equation_numbers: EquationNumbering::default(),
+            toc:              false,
+            list_of_figures:  false,
+            list_of_tables:   false,
+            lang:             None,
+            line_numbers:     false,
+            cover:            CoverOptions::default(),
+            base_dir:         None,
+            embed_images:     false,
+            embed_max_size:   10 * 1024 * 1024,
+            bibliography:     Vec::new(),
+            citation_style:   CitationStyle::default(),
+        }
+    }
+}
+
+// ─────────────────────────────────────────────
+//  CoverOptions
+// ─────────────────────────────────────────────
+
+/// Cover page fields.  `title`, `authors` and `date` are also the defaults
+/// for the document metadata; the front matter's values win over all of them.
+#[derive(Debug, Clone, Default)]
+pub struct CoverOptions {
+    /// Built-in layout (see [`crate::template::COVER_LAYOUTS`]) or the path of
+    /// a cover HTML template; `None` for no cover page.
+    pub layout: Option<String>,
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub organization: Option<String>,
+    pub date: Option<String>,
+    /// Logo image: a local path (embedded into the page) or a URL.
+    pub logo: Option<String>,
+    pub version: Option<String>,
+}
+
+/// Resolve a `--cover` value: `none` / `false` turn the cover off and `true`
+/// selects the `classic` layout; anything else is a layout or template path.
+pub fn cover_layout(value: &str) -> Option<String> {
+    match value.trim() {
+        "" | "none" | "false" => None,
+        "true" => Some("classic".to_string()),
+        other => Some(other.to_string()),
+    }
+}
+
 // ─────────────────────────────────────────────
 //  Assets directory resolution
 // ─────────────────────────────────────────────
@@ -151,3 +515,70 @@ pub fn resolve_assets_dir() -> std::path::PathBuf {
                 .join("assets")
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn lengths_in_every_unit() {
+        assert!(close(length_to_inches("25.4mm").unwrap(), 1.0));
+        assert!(close(length_to_inches("2.54 cm").unwrap(), 1.0));
+        assert!(close(length_to_inches("1in").unwrap(), 1.0));
+        assert!(close(length_to_inches("96px").unwrap(), 1.0));
+        assert!(close(length_to_inches("72pt").unwrap(), 1.0));
+        assert!(close(length_to_inches("50.8").unwrap(), 2.0));
+        assert_eq!(length_to_inches("1em"), None);
+        assert!(close(margin_to_inches("bogus"), 20.0 / 25.4));
+    }
+
+    #[test]
+    fn margin_shorthand() {
+        let mm = |v: f64| v / 25.4;
+        assert_eq!(Margins::parse("10mm"), Some(Margins::uniform(mm(10.0))));
+        assert_eq!(
+            Margins::parse("10mm 1in"),
+            Some(Margins { top: mm(10.0), right: 1.0, bottom: mm(10.0), left: 1.0 })
+        );
+        assert_eq!(
+            Margins::parse("1in 2in 3in"),
+            Some(Margins { top: 1.0, right: 2.0, bottom: 3.0, left: 2.0 })
+        );
+        assert_eq!(
+            Margins::parse("1in 2in 3in 4in"),
+            Some(Margins { top: 1.0, right: 2.0, bottom: 3.0, left: 4.0 })
+        );
+        assert_eq!(Margins::parse(""), None);
+        assert_eq!(Margins::parse("1in 2in 3in 4in 5in"), None);
+        assert_eq!(Margins::parse("1in auto"), None);
+    }
+
+    #[test]
+    fn paper_presets_and_custom_sizes() {
+        let (w, h) = paper_size("a4").unwrap();
+        assert!(close(w, 210.0 / 25.4) && close(h, 297.0 / 25.4));
+        assert_eq!(paper_size("Letter"), Some((8.5, 11.0)));
+        assert_eq!(paper_size("8.5in x 11in"), Some((8.5, 11.0)));
+        let (w, h) = paper_size("210x297mm").unwrap();
+        assert!(close(w, 210.0 / 25.4) && close(h, 297.0 / 25.4));
+        let (w, h) = paper_size("18cm×24cm").unwrap();
+        assert!(close(w, 18.0 / 2.54) && close(h, 24.0 / 2.54));
+        assert_eq!(paper_size("a9"), None);
+        assert_eq!(paper_size("0x297mm"), None);
+        assert_eq!(paper_size("210x297em"), None);
+    }
+
+    #[test]
+    fn byte_sizes() {
+        assert_eq!(byte_size("512"), Some(512));
+        assert_eq!(byte_size("500KB"), Some(500 * 1024));
+        assert_eq!(byte_size("1.5 MiB"), Some(3 * 512 * 1024));
+        assert_eq!(byte_size("2g"), Some(2 << 30));
+        assert_eq!(byte_size("10 TB"), None);
+        assert_eq!(byte_size("MB"), None);
+    }
+}
@@ -0,0 +1,209 @@
+//! config_file.rs — `md2pdf.toml` discovery, profiles, and merging into the
+//!                  command line.
+//!
+//! Keys are the long option names of `cli::Args` (`font-size`, `margin`,
+//! `chinese-font`, ...; `snake_case` is accepted too) plus `inputs` for the
+//! positional files.  Named profiles live in `[profile.<name>]` tables.
+//!
+//! Precedence, highest first:
+//!   1. options given on the command line
+//!   2. `--profile` table, then the top level of the project config
+//!      (`--config FILE`, or the nearest `md2pdf.toml` in the input's directory
+//!      or one of its parents)
+//!   3. the same for the user config (`<config dir>/md2pdf/md2pdf.toml`)
+//!   4. built-in defaults
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+use std::any::TypeId;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+pub const CONFIG_FILE_NAME: &str = "md2pdf.toml";
+
+/// Keys that only make sense on the command line.
+const CLI_ONLY: &[&str] = &["config", "profile"];
+
+/// String options that take a file as one of their forms (a header / footer
+/// or cover template, a logo image); like path options they are resolved
+/// against the config file, but only when that names an existing file, so
+/// presets, inline templates and URLs pass through unchanged.
+const FILE_OR_VALUE: &[&str] = &["header", "footer", "cover", "logo"];
+
+// ─────────────────────────────────────────────
+//  Discovery
+// ─────────────────────────────────────────────
+
+/// `<config dir>/md2pdf/md2pdf.toml`, if it exists.
+pub fn user_config_file() -> Option<PathBuf> {
+    dirs::config_dir()
+        .map(|d| d.join("md2pdf").join(CONFIG_FILE_NAME))
+        .filter(|p| p.is_file())
+}
+
+/// The nearest `md2pdf.toml` in `start` (a file's directory, or the directory
+/// itself) and its parents.
+pub fn project_config_file(start: &Path) -> Option<PathBuf> {
+    let start = fs::canonicalize(start).ok()?;
+    let dir = if start.is_dir() { start.as_path() } else { start.parent()? };
+    dir.ancestors()
+        .map(|d| d.join(CONFIG_FILE_NAME))
+        .find(|p| p.is_file())
+}
+
+// ─────────────────────────────────────────────
+//  Loading
+// ─────────────────────────────────────────────
+
+/// One parsed config file with the selected profile already applied.
+#[derive(Debug)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    settings: Table,
+}
+
+impl ConfigFile {
+    /// Read `path` and overlay `[profile.<profile>]` on its top-level keys.
+    /// A missing profile is not an error here; see [`check_profile`].
+    pub fn load(path: &Path, profile: Option<&str>) -> Result<(Self, bool), String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("无法读取配置文件 {}: {}", path.display(), e))?;
+        let mut settings: Table = text
+            .parse()
+            .map_err(|e| format!("配置文件格式错误 {}: {}", path.display(), e))?;
+
+        let profiles = match settings.remove("profile") {
+            None => Table::new(),
+            Some(Value::Table(t)) => t,
+            Some(_) => return Err(format!("{}: `profile` 必须是表", path.display())),
+        };
+        let mut found = false;
+        if let Some(name) = profile {
+            match profiles.get(name) {
+                Some(Value::Table(overrides)) => {
+                    found = true;
+                    settings.extend(overrides.clone());
+                }
+                Some(_) => {
+                    return Err(format!("{}: [profile.{}] 必须是表", path.display(), name))
+                }
+                None => {}
+            }
+        }
+
+        let path = path.to_path_buf();
+        Ok((Self { path, settings }, found))
+    }
+
+    fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+}
+
+/// Error unless at least one loaded file defines the requested profile.
+pub fn check_profile(profile: Option<&str>, found: bool) -> Result<(), String> {
+    match profile {
+        Some(name) if !found => Err(format!("配置文件中没有找到 [profile.{}]", name)),
+        _ => Ok(()),
+    }
+}
+
+// ─────────────────────────────────────────────
+//  Merging into the command line
+// ─────────────────────────────────────────────
+
+fn scalar_to_string(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err(format!("配置项 `{}` 的值类型不受支持", key)),
+    }
+}
+
+/// Build the argument list `cli::Args` is finally parsed from: `argv` plus
+/// every config setting whose option was not given on the command line.
+/// `files` are ordered lowest precedence first.  Relative paths in a config
+/// file are resolved against that file's directory.
+pub fn merged_args(
+    cmd: &Command,
+    matches: &ArgMatches,
+    files: &[ConfigFile],
+    argv: Vec<OsString>,
+) -> Result<Vec<OsString>, String> {
+    // Later files override earlier ones key by key.
+    let mut merged: Vec<(String, Value, &ConfigFile)> = Vec::new();
+    for file in files {
+        for (key, value) in &file.settings {
+            let key = key.replace('_', "-");
+            merged.retain(|(k, _, _)| *k != key);
+            merged.push((key, value.clone(), file));
+        }
+    }
+
+    let mut extra: Vec<OsString> = Vec::new();
+    let mut positionals: Vec<OsString> = Vec::new();
+
+    for (key, value, file) in merged {
+        let arg = cmd
+            .get_arguments()
+            .find(|a| {
+                a.get_long() == Some(key.as_str()) || (a.is_positional() && a.get_id() == key.as_str())
+            })
+            .filter(|a| !CLI_ONLY.contains(&a.get_id().as_str()))
+            .ok_or_else(|| format!("{}: 未知配置项 `{}`", file.path.display(), key))?;
+
+        if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+
+        let values = match &value {
+            Value::Array(items) => items
+                .iter()
+                .map(|v| scalar_to_string(&key, v))
+                .collect::<Result<Vec<_>, _>>()?,
+            other => vec![scalar_to_string(&key, other)?],
+        };
+        let is_path = arg.get_value_parser().type_id() == TypeId::of::<PathBuf>();
+        let maybe_path = FILE_OR_VALUE.contains(&arg.get_id().as_str());
+        let resolve = |v: String| -> OsString {
+            let relative = Path::new(&v).is_relative();
+            if relative && (is_path || (maybe_path && file.dir().join(&v).is_file())) {
+                file.dir().join(v).into_os_string()
+            } else {
+                v.into()
+            }
+        };
+
+        if arg.is_positional() {
+            positionals.extend(values.into_iter().map(resolve));
+        } else if arg.get_action().takes_values() {
+            for v in values {
+                let mut flag = OsString::from(format!("--{}=", key));
+                flag.push(resolve(v));
+                extra.push(flag);
+            }
+        } else {
+            // Switches: `true` turns them on, `false` leaves the default.
+            match value {
+                Value::Boolean(true) => extra.push(format!("--{}", key).into()),
+                Value::Boolean(false) => {}
+                _ => return Err(format!("配置项 `{}` 必须是 true 或 false", key)),
+            }
+        }
+    }
+
+    let mut args = argv;
+    let program = if args.is_empty() { OsString::from("md2pdf") } else { args.remove(0) };
+    let mut result = vec![program];
+    result.extend(extra);
+    result.extend(args);
+    if !positionals.is_empty() {
+        result.push("--".into());
+        result.extend(positionals);
+    }
+    Ok(result)
+}
@@ -1,10 +1,26 @@
-/// converter.rs — Launch headless Chrome and print the HTML to PDF.
-///                Mirrors converter.js → MarkdownToPdfConverter.generatePdf().
+//! converter.rs — Launch headless Chrome and print the HTML to PDF.
+//!                Mirrors converter.js → MarkdownToPdfConverter.generatePdf().
 
-use crate::config::PdfOptions;
-use headless_chrome::{Browser, LaunchOptions};
+use crate::config::{
+    byte_size, cover_layout, header_footer_template, margin_to_inches, normalize_with_unit, paper_size, resolve_assets_dir,
+    CitationStyle, EquationNumbering, Margins, MathRender, OutputFormat, PdfOptions, RenderOptions, StyleOptions,
+};
+use crate::highlight::theme_css;
+use crate::katex_assets::KatexAssets;
+use crate::katex_engine::KatexEngine;
+use crate::pdf;
+use crate::renderer::{render, split_front_matter, FrontMatter};
+use crate::template::{cover_html, generate_html_document, header_footer_html, uses_section};
+use crate::theme::{theme_exists, theme_names};
+use headless_chrome::types::PrintToPdfOptions;
+use headless_chrome::{Browser, LaunchOptions, Tab};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 use thiserror::Error;
 
 // ─────────────────────────────────────────────
@@ -25,24 +41,11 @@ pub enum AppError {
 //  PDF generation
 // ─────────────────────────────────────────────
 
-/// Write the HTML to a sibling temp file, load it in headless Chrome, print to PDF.
-pub fn generate_pdf(
-    html: &str,
-    output_path: &Path,
-    pdf_opts: &PdfOptions,
-    chrome_path: Option<&Path>,
-) -> Result<(), AppError> {
-    let html_path = output_path.with_extension("html");
-    fs::write(&html_path, html)?;
-
-    let path_str = html_path.to_string_lossy().replace('\\', "/");
-    let file_url = if path_str.starts_with('/') {
-        format!("file://{}", path_str)
-    } else {
-        format!("file:///{}", path_str)
-    };
-
-    println!("[1/5] 正在启动浏览器 (Headless Chrome)...");
+/// Launch a headless Chrome suitable for printing.
+pub fn launch_browser(chrome_path: Option<&Path>, progress: bool) -> Result<Browser, AppError> {
+    if progress {
+        eprintln!("[1/5] 正在启动浏览器 (Headless Chrome)...");
+    }
 
     let mut builder = LaunchOptions::default_builder();
     builder
@@ -69,47 +72,839 @@ pub fn generate_pdf(
         .build()
         .map_err(|e| AppError::Browser(format!("launch options error: {}", e)))?;
 
-    let browser = Browser::new(launch_opts)
-        .map_err(|e| AppError::Browser(format!("cannot start browser: {}", e)))?;
+    Browser::new(launch_opts)
+        .map_err(|e| AppError::Browser(format!("cannot start browser: {}", e)))
+}
+
+/// Chrome's print options for `pdf_opts`, optionally with expanded
+/// header / footer HTML and a page range.
+fn print_options(
+    pdf_opts: &PdfOptions,
+    header_footer: Option<(String, String)>,
+    page_ranges: Option<String>,
+) -> PrintToPdfOptions {
+    let display_header_footer = header_footer.is_some();
+    let (header_template, footer_template) = match header_footer {
+        Some((header, footer)) => (Some(header), Some(footer)),
+        None => (None, None),
+    };
+    PrintToPdfOptions {
+        print_background: Some(true),
+        paper_width:  Some(pdf_opts.paper_width),
+        paper_height: Some(pdf_opts.paper_height),
+        margin_top:    Some(pdf_opts.margins.top),
+        margin_right:  Some(pdf_opts.margins.right),
+        margin_bottom: Some(pdf_opts.margins.bottom),
+        margin_left:   Some(pdf_opts.margins.left),
+        landscape: Some(pdf_opts.landscape),
+        display_header_footer: Some(display_header_footer),
+        header_template,
+        footer_template,
+        page_ranges,
+        ..Default::default()
+    }
+}
+
+/// Expanded header and footer for one print.  When only one of them is set
+/// the other is blank; Chrome would otherwise print its default URL / date.
+fn header_footer(
+    pdf_opts: &PdfOptions,
+    meta: &FrontMatter,
+    section: Option<&str>,
+    pages: Option<u32>,
+) -> Option<(String, String)> {
+    if pdf_opts.header_template.is_none() && pdf_opts.footer_template.is_none() {
+        return None;
+    }
+    let expand = |template: &Option<String>| match template {
+        Some(t) => header_footer_html(t, meta, section, pages, &pdf_opts.margins),
+        None => "<span></span>".to_string(),
+    };
+    Some((expand(&pdf_opts.header_template), expand(&pdf_opts.footer_template)))
+}
+
+fn print_tab(tab: &Tab, options: PrintToPdfOptions) -> Result<Vec<u8>, AppError> {
+    tab.print_to_pdf(Some(options))
+        .map_err(|e| AppError::Pdf(format!("print to pdf failed: {}", e)))
+}
+
+/// Put a 1px self-link at the start of every heading so that Chrome records a
+/// named destination (`md2pdf-heading-<i>`) for it, and list the headings.
+/// Headings of a custom cover page are not part of the document outline.
+const MARK_HEADINGS_JS: &str = r#"
+(function () {
+  var marks = [];
+  document.querySelectorAll('h1, h2, h3, h4, h5, h6').forEach(function (h, i) {
+    if (h.closest('.cover')) return;
+    var a = document.createElement('a');
+    a.id = 'md2pdf-heading-' + i;
+    a.href = '#' + a.id;
+    a.style.cssText = 'display:inline-block;width:1px;height:1em;margin-left:-1px;vertical-align:top';
+    h.insertBefore(a, h.firstChild);
+    var copy = h.cloneNode(true);
+    copy.querySelectorAll('.katex-mathml, .footnote-reference').forEach(function (e) { e.remove(); });
+    var text = copy.textContent.replace(/\s+/g, ' ').trim();
+    marks.push({ level: Number(h.tagName.charAt(1)), text: text, id: h.id, mark: a.id });
+  });
+  return JSON.stringify(marks);
+})()
+"#;
+
+/// A heading of the loaded page and the page it was printed on.
+#[derive(Debug, Clone, Deserialize)]
+struct Heading {
+    level: u8,
+    text: String,
+    /// The heading's own `id` attribute (empty if none).
+    id: String,
+    /// Named destination placed on the heading by [`MARK_HEADINGS_JS`].
+    mark: String,
+    #[serde(skip)]
+    page: Option<u32>,
+}
+
+fn mark_headings(tab: &Tab) -> Result<Vec<Heading>, AppError> {
+    let value = tab
+        .evaluate(MARK_HEADINGS_JS, false)
+        .map_err(|e| AppError::Browser(format!("cannot mark headings: {}", e)))?
+        .value;
+    Ok(value
+        .as_ref()
+        .and_then(|v| v.as_str())
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default())
+}
+
+/// Fill in the page of every marked heading by printing the page once.
+/// Returns the page count of that print.
+fn locate_headings(
+    tab: &Tab,
+    pdf_opts: &PdfOptions,
+    headings: &mut [Heading],
+) -> Result<(u32, HashMap<String, u32>), AppError> {
+    // Headers and footers are drawn inside the margins, so leaving them out
+    // does not change where anything lands.
+    let layout = print_tab(tab, print_options(pdf_opts, None, None))?;
+    let destinations = pdf::destination_pages(&layout)?;
+    for heading in headings.iter_mut() {
+        heading.page = destinations.get(&heading.mark).copied();
+    }
+    Ok((pdf::page_count(&layout)?, destinations))
+}
+
+/// Fill the `.toc-page` cells of the table of contents and the lists of
+/// figures and tables from `pages` (target id → page number) and show the
+/// dot leaders.
+const FILL_TOC_JS: &str = r#"
+(function (pages) {
+  document.querySelectorAll('.toc').forEach(function (toc) { toc.classList.add('toc-with-pages'); });
+  document.querySelectorAll('.toc-page').forEach(function (cell) {
+    var page = pages[cell.getAttribute('data-target')];
+    cell.textContent = page === undefined ? '' : String(page);
+  });
+})
+"#;
+
+/// Write the page numbers of a located layout into the table of contents and
+/// the lists of figures and tables.  Headings are found by their marks; the
+/// other entries by the named destinations Chrome made for their links.
+fn fill_toc(tab: &Tab, headings: &[Heading], destinations: &HashMap<String, u32>) -> Result<(), AppError> {
+    let mut pages: serde_json::Map<String, serde_json::Value> = destinations
+        .iter()
+        .map(|(id, page)| (id.clone(), (*page).into()))
+        .collect();
+    pages.extend(
+        headings
+            .iter()
+            .filter(|h| !h.id.is_empty())
+            .filter_map(|h| Some((h.id.clone(), h.page?.into()))),
+    );
+    let call = format!("{}({})", FILL_TOC_JS.trim(), serde_json::Value::Object(pages));
+    tab.evaluate(&call, false)
+        .map_err(|e| AppError::Browser(format!("cannot fill table of contents: {}", e)))?;
+    Ok(())
+}
+
+/// Running section title of every page: the first `h1`/`h2` starting on the
+/// page, else the last one before it.
+fn page_sections(headings: &[Heading], pages: u32) -> Vec<Option<String>> {
+    let mut starts = headings
+        .iter()
+        .filter(|h| h.level <= 2)
+        .filter_map(|h| Some((h.page?, h.text.as_str())))
+        .peekable();
+    let mut current: Option<&str> = None;
+    let mut sections = Vec::new();
+    for page in 1..=pages {
+        let mut first_on_page = None;
+        while let Some((_, text)) = starts.next_if(|(p, _)| *p <= page) {
+            first_on_page = first_on_page.or(Some(text));
+            current = Some(text);
+        }
+        sections.push(first_on_page.or(current).map(str::to_string));
+    }
+    sections
+}
 
-    println!("[2/5] 正在创建新标签页...");
+/// Print page runs that need different headers / footers separately and
+/// merge the parts: with a `{section}` template (`by_section`) each run of
+/// pages sharing a section gets its own, and a `cover` page gets none.
+fn print_in_parts(
+    tab: &Tab,
+    pdf_opts: &PdfOptions,
+    meta: &FrontMatter,
+    headings: &mut [Heading],
+    by_section: bool,
+    cover: bool,
+) -> Result<Vec<u8>, AppError> {
+    let (pages, _) = locate_headings(tab, pdf_opts, headings)?;
+    let sections = if by_section {
+        page_sections(headings, pages)
+    } else {
+        vec![None; pages as usize]
+    };
+    let mut page_header_footer: Vec<Option<(String, String)>> = sections
+        .iter()
+        .map(|section| header_footer(pdf_opts, meta, section.as_deref(), Some(pages)))
+        .collect();
+    if cover {
+        if let Some(first) = page_header_footer.first_mut() {
+            *first = None;
+        }
+    }
+
+    let mut parts = Vec::new();
+    let mut first = 1;
+    for page in 1..=pages {
+        let header_footer = &page_header_footer[page as usize - 1];
+        if page < pages && page_header_footer[page as usize] == *header_footer {
+            continue;
+        }
+        let range = format!("{}-{}", first, page);
+        parts.push(print_tab(tab, print_options(pdf_opts, header_footer.clone(), Some(range)))?);
+        first = page + 1;
+    }
+
+    match parts.len() {
+        0 => print_tab(tab, print_options(pdf_opts, header_footer(pdf_opts, meta, None, None), None)),
+        1 => Ok(parts.remove(0)),
+        _ => pdf::merge(&parts),
+    }
+}
+
+/// `file://` URL of an absolute path.
+fn file_url(path: &Path) -> String {
+    let path_str = path.to_string_lossy().replace('\\', "/");
+    if path_str.starts_with('/') {
+        format!("file://{}", path_str)
+    } else {
+        format!("file:///{}", path_str)
+    }
+}
+
+/// Rewrite relative `src` / `href` attributes (images, links to local files)
+/// into absolute `file://` URLs under `base_dir`.  The page is printed from a
+/// temporary file elsewhere, where relative paths would not resolve; a
+/// `<base href>` would also redirect the `#id` links the PDF needs.
+fn absolutize_urls(html: &str, base_dir: &Path) -> String {
+    let base = file_url(base_dir)
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('#', "%23")
+        .replace('?', "%3F");
+    let base = base.trim_end_matches('/');
+    let re = Regex::new(r#"(<(?:img|a|source|video|audio|iframe)\b[^>]*?\s(?:src|href)=")([^"]*)""#)
+        .expect("valid regex");
+    re.replace_all(html, |cap: &regex::Captures| {
+        let url = &cap[2];
+        let is_relative = !url.is_empty()
+            && !url.starts_with(['#', '/'])
+            && !url.contains(':');
+        if is_relative {
+            format!("{}{}/{}\"", &cap[1], base, url)
+        } else {
+            cap[0].to_string()
+        }
+    })
+    .into_owned()
+}
+
+/// Write the HTML to a temp file inside `work_dir`, load it in a new tab of
+/// `browser`, print to PDF and return the PDF bytes.
+/// `meta` fills the `{title}` / `{date}` header and footer placeholders.
+/// The tab is closed and the temp file removed on return.
+/// `progress` prints the `[n/5]` step messages (to stderr, so that they never
+/// mix with a document written to stdout).
+pub fn print_pdf(
+    browser: &Browser,
+    html: &str,
+    work_dir: &Path,
+    pdf_opts: &PdfOptions,
+    meta: &FrontMatter,
+    progress: bool,
+) -> Result<Vec<u8>, AppError> {
+    let step = |msg: String| {
+        if progress {
+            eprintln!("{}", msg);
+        }
+    };
+
+    let mut html_file = tempfile::Builder::new()
+        .prefix(".md2pdf-")
+        .suffix(".html")
+        .tempfile_in(work_dir)?;
+    html_file.write_all(html.as_bytes())?;
+    html_file.flush()?;
+    let html_path = html_file.path();
+
+    let file_url = file_url(html_path);
+
+    step("[2/5] 正在创建新标签页...".to_string());
     let tab = browser
         .new_tab()
         .map_err(|e| AppError::Browser(format!("new tab failed: {}", e)))?;
 
     tab.set_default_timeout(std::time::Duration::from_secs(3600 * 24 * 365 * 100));
 
-    println!("[3/5] 正在加载页面: {} ...", file_url);
-    tab.navigate_to(&file_url)
-        .map_err(|e| AppError::Browser(format!("navigation failed: {}", e)))?;
-    tab.wait_until_navigated()
-        .map_err(|e| AppError::Browser(format!("wait navigated failed: {}", e)))?;
+    let result = (|| {
+        step(format!("[3/5] 正在加载页面: {} ...", file_url));
+        tab.navigate_to(&file_url)
+            .map_err(|e| AppError::Browser(format!("navigation failed: {}", e)))?;
+        tab.wait_until_navigated()
+            .map_err(|e| AppError::Browser(format!("wait navigated failed: {}", e)))?;
 
-    println!("[4/5] 正在等待数学公式动态渲染完成...");
-    tab.wait_for_element("#render-complete")
-        .map_err(|e| AppError::Browser(format!("wait for rendering complete failed: {}", e)))?;
+        step("[4/5] 正在等待数学公式动态渲染完成...".to_string());
+        tab.wait_for_element("#render-complete")
+            .map_err(|e| AppError::Browser(format!("wait for rendering complete failed: {}", e)))?;
 
-    println!("[5/5] 正在生成 PDF...");
-    let pdf_print_opts = headless_chrome::types::PrintToPdfOptions {
-        print_background: Some(true),
-        paper_width:  Some(8.27),
-        paper_height: Some(11.69),
-        margin_top:    Some(pdf_opts.margin_inches),
-        margin_right:  Some(pdf_opts.margin_inches),
-        margin_bottom: Some(pdf_opts.margin_inches),
-        margin_left:   Some(pdf_opts.margin_inches),
-        landscape: Some(pdf_opts.landscape),
-        ..Default::default()
-    };
+        step("[5/5] 正在生成 PDF...".to_string());
+        let by_section = uses_section(pdf_opts.header_template.as_deref())
+            || uses_section(pdf_opts.footer_template.as_deref());
+        let has_toc = html.contains(r#"class="toc-page""#);
+        let mut headings = if by_section || has_toc || pdf_opts.outline {
+            mark_headings(&tab)?
+        } else {
+            Vec::new()
+        };
 
-    let pdf_data = tab
-        .print_to_pdf(Some(pdf_print_opts))
-        .map_err(|e| AppError::Pdf(format!("print to pdf failed: {}", e)))?;
+        // Page numbers only take a little room at the end of each entry, so
+        // filling them in does not move the headings they point to.
+        if has_toc {
+            let (_, destinations) = locate_headings(&tab, pdf_opts, &mut headings)?;
+            fill_toc(&tab, &headings, &destinations)?;
+        }
 
-    fs::write(output_path, pdf_data)?;
+        let cover = html.contains(r#"<section class="cover "#)
+            && (pdf_opts.header_template.is_some() || pdf_opts.footer_template.is_some());
+        let pdf_data = if by_section || cover {
+            print_in_parts(&tab, pdf_opts, meta, &mut headings, by_section, cover)?
+        } else {
+            print_tab(&tab, print_options(pdf_opts, header_footer(pdf_opts, meta, None, None), None))?
+        };
 
-    // Clean up temp HTML
-    let _ = fs::remove_file(&html_path);
+        // Chrome's own `generateDocumentOutline` is only available in recent
+        // versions and lost when page ranges are merged, so build it here.
+        let pdf_data = if pdf_opts.outline {
+            let items: Vec<pdf::OutlineItem> = headings
+                .into_iter()
+                .filter(|h| !h.text.is_empty())
+                .map(|h| pdf::OutlineItem { level: h.level, title: h.text, destination: h.mark })
+                .collect();
+            pdf::add_outline(&pdf_data, &items)?
+        } else {
+            pdf_data
+        };
 
-    Ok(())
+        // Chrome only fills in the title from `<title>`.
+        pdf::set_metadata(&pdf_data, &pdf_metadata(meta))
+    })();
+
+    // Keep a long-lived browser lean: one tab per conversion.
+    let _ = tab.close(false);
+
+    result
+}
+
+/// PDF metadata from the front matter (with the CLI fallbacks filled in).
+fn pdf_metadata(meta: &FrontMatter) -> pdf::Metadata {
+    let non_empty = |s: &Option<String>| s.as_ref().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    pdf::Metadata {
+        title: non_empty(&meta.title),
+        authors: meta.authors.clone(),
+        subject: non_empty(&meta.subject),
+        keywords: meta.keywords.clone(),
+    }
+}
+
+/// One-shot conversion: launch a browser, print, and shut the browser down.
+pub fn generate_pdf(
+    html: &str,
+    work_dir: &Path,
+    pdf_opts: &PdfOptions,
+    meta: &FrontMatter,
+    chrome_path: Option<&Path>,
+) -> Result<Vec<u8>, AppError> {
+    let browser = launch_browser(chrome_path, true)?;
+    print_pdf(&browser, html, work_dir, pdf_opts, meta, true)
+}
+
+// ─────────────────────────────────────────────
+//  Per-document options
+// ─────────────────────────────────────────────
+
+/// A file named in the front matter: relative paths are relative to the
+/// document's directory, not the current one.
+fn document_path(base_dir: Option<&Path>, value: &str) -> PathBuf {
+    let path = Path::new(value);
+    match base_dir {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The front matter's cover `logo`: URLs are kept, files resolved like
+/// [`document_path`].
+fn document_logo(base_dir: Option<&Path>, value: &str) -> String {
+    if value.contains("://") || value.starts_with("data:") {
+        return value.to_string();
+    }
+    document_path(base_dir, value).to_string_lossy().into_owned()
+}
+
+/// The front matter's `theme`: a built-in theme name, or a theme directory
+/// resolved like [`document_path`].  `None` when neither exists.
+fn document_theme(base_dir: Option<&Path>, value: &str) -> Option<String> {
+    if theme_names().iter().any(|name| name.eq_ignore_ascii_case(value)) {
+        return Some(value.to_string());
+    }
+    let dir = document_path(base_dir, value);
+    theme_exists(&dir.to_string_lossy()).then(|| dir.to_string_lossy().into_owned())
+}
+
+/// Override style / PDF / render options with the front matter's option keys
+/// (same names and units as the CLI flags).  Unknown keys are ignored.
+/// Relative `theme`, `css`, `template`, `bibliography` and `logo` paths are
+/// taken relative to the document.
+fn apply_front_matter(
+    fm: &FrontMatter,
+    style: &mut StyleOptions,
+    pdf: &mut PdfOptions,
+    render_opts: &mut RenderOptions,
+) {
+    for (key, value) in &fm.options {
+        match key.as_str() {
+            "theme" => match document_theme(render_opts.base_dir.as_deref(), value) {
+                Some(theme) => style.theme = theme,
+                None => eprintln!("Warning: unknown front matter theme: {}", value),
+            },
+            "font-size"         => style.font_size = Some(normalize_with_unit(value, "px")),
+            "chinese-font"      => style.chinese_font = Some(value.clone()),
+            "font-weight"       => style.font_weight = Some(value.clone()),
+            "line-spacing"      => style.line_spacing = Some(value.clone()),
+            "paragraph-spacing" => style.paragraph_spacing = Some(normalize_with_unit(value, "em")),
+            "math-spacing"      => style.math_spacing = Some(normalize_with_unit(value, "px")),
+            "code-theme" => match theme_css(value) {
+                Some(_) => style.code_theme = value.clone(),
+                None => eprintln!("Warning: unsupported front matter code-theme: {}", value),
+            },
+            "css"      => style.css_files.push(document_path(render_opts.base_dir.as_deref(), value)),
+            "template" => style.html_template = Some(document_path(render_opts.base_dir.as_deref(), value)),
+            "cover"        => render_opts.cover.layout = cover_layout(value),
+            "subtitle"     => render_opts.cover.subtitle = Some(value.clone()),
+            "organization" => render_opts.cover.organization = Some(value.clone()),
+            "logo"         => render_opts.cover.logo = Some(document_logo(render_opts.base_dir.as_deref(), value)),
+            "version"      => render_opts.cover.version = Some(value.clone()),
+            "embed-images" => match value.parse() {
+                Ok(b) => render_opts.embed_images = b,
+                Err(_) => eprintln!("Warning: front matter `embed-images` must be true or false"),
+            },
+            "embed-max-size" => match byte_size(value) {
+                Some(n) => render_opts.embed_max_size = n,
+                None => eprintln!("Warning: invalid front matter embed-max-size: {}", value),
+            },
+            "line-numbers" => match value.parse() {
+                Ok(b) => render_opts.line_numbers = b,
+                Err(_) => eprintln!("Warning: front matter `line-numbers` must be true or false"),
+            },
+            "margin" => match Margins::parse(&normalize_with_unit(value, "mm")) {
+                Some(m) => pdf.margins = m,
+                None => eprintln!("Warning: invalid front matter margin: {}", value),
+            },
+            "margin-top"    => pdf.margins.top = margin_to_inches(&normalize_with_unit(value, "mm")),
+            "margin-right"  => pdf.margins.right = margin_to_inches(&normalize_with_unit(value, "mm")),
+            "margin-bottom" => pdf.margins.bottom = margin_to_inches(&normalize_with_unit(value, "mm")),
+            "margin-left"   => pdf.margins.left = margin_to_inches(&normalize_with_unit(value, "mm")),
+            "paper" => match paper_size(value) {
+                Some((w, h)) => (pdf.paper_width, pdf.paper_height) = (w, h),
+                None => eprintln!("Warning: unsupported front matter paper size: {}", value),
+            },
+            "landscape" => match value.parse() {
+                Ok(b) => pdf.landscape = b,
+                Err(_) => eprintln!("Warning: front matter `landscape` must be true or false"),
+            },
+            "outline" => match value.parse() {
+                Ok(b) => pdf.outline = b,
+                Err(_) => eprintln!("Warning: front matter `outline` must be true or false"),
+            },
+            "header" | "footer" => match header_footer_template(value) {
+                Ok(t) if key == "header" => pdf.header_template = t,
+                Ok(t) => pdf.footer_template = t,
+                Err(e) => eprintln!("Warning: cannot read front matter {}: {}", key, e),
+            },
+            "toc" => match value.parse() {
+                Ok(b) => render_opts.toc = b,
+                Err(_) => eprintln!("Warning: front matter `toc` must be true or false"),
+            },
+            "list-of-figures" => match value.parse() {
+                Ok(b) => render_opts.list_of_figures = b,
+                Err(_) => eprintln!("Warning: front matter `list-of-figures` must be true or false"),
+            },
+            "list-of-tables" => match value.parse() {
+                Ok(b) => render_opts.list_of_tables = b,
+                Err(_) => eprintln!("Warning: front matter `list-of-tables` must be true or false"),
+            },
+            "math-render" => match MathRender::parse(value) {
+                Some(m) => render_opts.math = m,
+                None => eprintln!("Warning: unsupported front matter math-render: {}", value),
+            },
+            "equation-numbers" => match EquationNumbering::parse(value) {
+                Some(n) => render_opts.equation_numbers = n,
+                None => eprintln!("Warning: unsupported front matter equation-numbers: {}", value),
+            },
+            "bibliography" => render_opts.bibliography.push(document_path(render_opts.base_dir.as_deref(), value)),
+            "citation-style" => match CitationStyle::parse(value) {
+                Some(s) => render_opts.citation_style = s,
+                None => eprintln!("Warning: unsupported front matter citation-style: {}", value),
+            },
+            _ => {}
+        }
+    }
+}
+
+/// A rendered HTML document and the options it is printed with.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub html: String,
+    /// Front matter, with `title` filled in from the fallback if it had none.
+    pub meta: FrontMatter,
+    pub pdf_opts: PdfOptions,
+    /// Directory relative URLs in the document point into (the input file's
+    /// directory); the current directory when `None`.
+    pub base_dir: Option<PathBuf>,
+}
+
+// ─────────────────────────────────────────────
+//  Converter
+// ─────────────────────────────────────────────
+
+/// Headless Chrome launched on first use and kept alive for later conversions.
+#[derive(Default)]
+struct SharedBrowser(Mutex<Option<Browser>>);
+
+impl std::fmt::Debug for SharedBrowser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let running = self.0.lock().map(|b| b.is_some()).unwrap_or(false);
+        f.debug_struct("SharedBrowser").field("running", &running).finish()
+    }
+}
+
+/// Server-side KaTeX engine, started on first use and shared by every
+/// conversion; `None` when it cannot start.
+#[derive(Default)]
+struct SharedKatex(OnceLock<Option<KatexEngine>>);
+
+impl std::fmt::Debug for SharedKatex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let started = self.0.get().is_some_and(Option::is_some);
+        f.debug_struct("SharedKatex").field("started", &started).finish()
+    }
+}
+
+/// Reusable Markdown → HTML / PDF converter.  Mirrors `MarkdownToPdfConverter`.
+///
+/// KaTeX assets are loaded once by [`ConverterBuilder::build`] and shared by
+/// every conversion.  The first PDF conversion launches headless Chrome; later
+/// ones (also from other threads or clones of this converter) reuse it and
+/// only open a new tab.
+#[derive(Debug, Clone)]
+pub struct Converter {
+    style_opts: StyleOptions,
+    pdf_opts: PdfOptions,
+    render_opts: RenderOptions,
+    chrome_path: Option<PathBuf>,
+    progress: bool,
+    assets_dir: PathBuf,
+    katex: KatexAssets,
+    katex_engine: Arc<SharedKatex>,
+    browser: Arc<SharedBrowser>,
+}
+
+impl Converter {
+    pub fn builder() -> ConverterBuilder {
+        ConverterBuilder::default()
+    }
+
+    pub fn style_options(&self) -> &StyleOptions {
+        &self.style_opts
+    }
+
+    pub fn pdf_options(&self) -> &PdfOptions {
+        &self.pdf_opts
+    }
+
+    pub fn render_options(&self) -> &RenderOptions {
+        &self.render_opts
+    }
+
+    pub fn assets_dir(&self) -> &Path {
+        &self.assets_dir
+    }
+
+    /// Re-read the KaTeX assets, e.g. after files in the assets directory changed.
+    pub fn reload_assets(&mut self) {
+        self.katex = KatexAssets::load(&self.assets_dir);
+        self.katex_engine = Arc::default();
+    }
+
+    /// Replace the PDF options, e.g. after a `--header` / `--footer` template
+    /// file changed.
+    pub fn set_pdf_options(&mut self, opts: PdfOptions) {
+        self.pdf_opts = opts;
+    }
+
+    /// Render markdown to a complete, self-contained HTML document.
+    ///
+    /// YAML front matter is stripped; its `title` (else the cover title, else
+    /// `title`), `lang`, `author`, `date`, `subject` and `keywords` go into the
+    /// document head and the PDF metadata, and option keys such as
+    /// `font-size` or `margin` override this converter's options for this
+    /// document only.  With a cover layout the cover page is put before the
+    /// content.
+    pub fn render_document(&self, markdown: &str, title: &str) -> Document {
+        self.render_document_in(markdown, title, None)
+    }
+
+    /// Like [`Converter::render_document`], for markdown read from a file in
+    /// `base_dir`: relative image paths are resolved against that directory.
+    pub fn render_document_in(&self, markdown: &str, title: &str, base_dir: Option<&Path>) -> Document {
+        let (front_matter, body) = split_front_matter(markdown);
+        let mut meta = front_matter.unwrap_or_default();
+        let cover = &self.render_opts.cover;
+        if meta.title.is_none() {
+            meta.title = Some(cover.title.clone().unwrap_or_else(|| title.to_string()));
+        }
+        if meta.authors.is_empty() {
+            meta.authors = cover.authors.clone();
+        }
+        if meta.date.is_none() {
+            meta.date = cover.date.clone();
+        }
+        if meta.lang.is_none() {
+            meta.lang = self.render_opts.lang.clone();
+        }
+        if meta.subject.is_none() {
+            meta.subject = self.pdf_opts.subject.clone();
+        }
+        if meta.keywords.is_empty() {
+            meta.keywords = self.pdf_opts.keywords.clone();
+        }
+
+        let mut style_opts = self.style_opts.clone();
+        let mut pdf_opts = self.pdf_opts.clone();
+        let mut render_opts = self.render_opts.clone();
+        render_opts.lang = meta.lang.clone();
+        if let Some(dir) = base_dir {
+            render_opts.base_dir = Some(dir.to_path_buf());
+        }
+        apply_front_matter(&meta, &mut style_opts, &mut pdf_opts, &mut render_opts);
+
+        let engine = match render_opts.math {
+            MathRender::Server => self.katex_engine(),
+            MathRender::Client => None,
+        };
+        let (mut html_body, client_math) = render(body, &render_opts, engine);
+        let page_height = if pdf_opts.landscape { pdf_opts.paper_width } else { pdf_opts.paper_height }
+            - pdf_opts.margins.top
+            - pdf_opts.margins.bottom;
+        if let Some(cover) = cover_html(&render_opts.cover, &meta, page_height) {
+            html_body.insert_str(0, &cover);
+        }
+        let html = generate_html_document(
+            &html_body,
+            &meta,
+            &self.katex.css,
+            &self.katex.js,
+            &self.katex.auto_render_js,
+            &style_opts,
+            if client_math { MathRender::Client } else { render_opts.math },
+        );
+        Document { html, meta, pdf_opts, base_dir: render_opts.base_dir }
+    }
+
+    /// The server-side KaTeX engine, started on first use; a failure is
+    /// reported once and math then falls back to client-side rendering.
+    fn katex_engine(&self) -> Option<&KatexEngine> {
+        self.katex_engine
+            .0
+            .get_or_init(|| match KatexEngine::new(&self.katex.js) {
+                Ok(engine) => Some(engine),
+                Err(e) => {
+                    eprintln!("Warning: server-side math rendering unavailable ({}), using client-side KaTeX", e);
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    /// Render markdown to a complete HTML document (see [`Converter::render_document`]).
+    pub fn to_html(&self, markdown: &str, title: &str) -> String {
+        self.render_document(markdown, title).html
+    }
+
+    /// Render markdown straight to PDF bytes.
+    pub fn to_pdf(&self, markdown: &str, title: &str) -> Result<Vec<u8>, AppError> {
+        let doc = self.render_document(markdown, title);
+        self.document_to_pdf(&doc, None)
+    }
+
+    /// Print a rendered document with its own PDF options.
+    /// See [`Converter::html_to_pdf`] for `work_dir`.
+    /// Relative image and link URLs are made absolute against the document's
+    /// `base_dir`, so they resolve wherever the PDF is written.
+    pub fn document_to_pdf(&self, doc: &Document, work_dir: Option<&Path>) -> Result<Vec<u8>, AppError> {
+        // A bare file name's parent is "", which `absolute` rejects.
+        let base_dir = doc.base_dir.as_deref().filter(|d| !d.as_os_str().is_empty());
+        let base_dir = std::path::absolute(base_dir.unwrap_or(Path::new(".")))?;
+        let html = absolutize_urls(&doc.html, &base_dir);
+        self.print_html(&html, &doc.pdf_opts, &doc.meta, work_dir)
+    }
+
+    /// Print an HTML document produced by [`Converter::to_html`] to PDF bytes.
+    /// The temporary HTML file is written to `work_dir` (system temp dir if `None`),
+    /// which is where relative resource paths are resolved from.
+    pub fn html_to_pdf(&self, html: &str, work_dir: Option<&Path>) -> Result<Vec<u8>, AppError> {
+        self.print_html(html, &self.pdf_opts, &FrontMatter::default(), work_dir)
+    }
+
+    fn print_html(
+        &self,
+        html: &str,
+        pdf_opts: &PdfOptions,
+        meta: &FrontMatter,
+        work_dir: Option<&Path>,
+    ) -> Result<Vec<u8>, AppError> {
+        let temp_dir = std::env::temp_dir();
+        let work_dir = work_dir.unwrap_or(&temp_dir);
+        let browser = self.browser()?;
+        print_pdf(&browser, html, work_dir, pdf_opts, meta, self.progress)
+    }
+
+    /// Convert one markdown file and write the result to `output`.
+    /// The document title defaults to the input's file stem.
+    pub fn convert_file(
+        &self,
+        input: &Path,
+        output: &Path,
+        format: OutputFormat,
+    ) -> Result<(), AppError> {
+        let markdown = fs::read_to_string(input)?;
+        let title = input
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Markdown to PDF");
+        let doc = self.render_document_in(&markdown, title, input.parent());
+        match format {
+            OutputFormat::Html => fs::write(output, doc.html)?,
+            OutputFormat::Pdf => {
+                let pdf_data = self.document_to_pdf(&doc, output.parent())?;
+                fs::write(output, pdf_data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Launch the shared browser now instead of on the first PDF conversion.
+    pub fn warm_up(&self) -> Result<(), AppError> {
+        self.browser().map(|_| ())
+    }
+
+    /// Handle to the shared browser, (re)launching it if it is not running.
+    fn browser(&self) -> Result<Browser, AppError> {
+        let mut slot = self
+            .browser
+            .0
+            .lock()
+            .map_err(|_| AppError::Browser("browser lock poisoned".to_string()))?;
+        if let Some(browser) = slot.as_ref() {
+            if browser.get_version().is_ok() {
+                return Ok(browser.clone());
+            }
+            eprintln!("Warning: headless Chrome stopped responding, restarting it");
+        }
+        let browser = launch_browser(self.chrome_path.as_deref(), self.progress)?;
+        *slot = Some(browser.clone());
+        Ok(browser)
+    }
+}
+
+/// Builder for [`Converter`].
+#[derive(Debug, Default)]
+pub struct ConverterBuilder {
+    style_opts: StyleOptions,
+    pdf_opts: PdfOptions,
+    render_opts: RenderOptions,
+    chrome_path: Option<PathBuf>,
+    assets_dir: Option<PathBuf>,
+    quiet: bool,
+}
+
+impl ConverterBuilder {
+    pub fn style_options(mut self, opts: StyleOptions) -> Self {
+        self.style_opts = opts;
+        self
+    }
+
+    pub fn pdf_options(mut self, opts: PdfOptions) -> Self {
+        self.pdf_opts = opts;
+        self
+    }
+
+    pub fn render_options(mut self, opts: RenderOptions) -> Self {
+        self.render_opts = opts;
+        self
+    }
+
+    /// Chrome executable to launch (auto-detected when unset).
+    pub fn chrome_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.chrome_path = Some(path.into());
+        self
+    }
+
+    /// Directory holding `katex/` (defaults to [`resolve_assets_dir`]).
+    pub fn assets_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.assets_dir = Some(dir.into());
+        self
+    }
+
+    /// Print the `[n/5]` PDF step messages to stderr (on by default).
+    pub fn progress(mut self, enabled: bool) -> Self {
+        self.quiet = !enabled;
+        self
+    }
+
+    /// Load the KaTeX assets and build the converter.
+    pub fn build(self) -> Converter {
+        let assets_dir = self.assets_dir.unwrap_or_else(resolve_assets_dir);
+        Converter {
+            style_opts: self.style_opts,
+            pdf_opts: self.pdf_opts,
+            render_opts: self.render_opts,
+            chrome_path: self.chrome_path,
+            progress: !self.quiet,
+            katex: KatexAssets::load(&assets_dir),
+            katex_engine: Arc::default(),
+            assets_dir,
+            browser: Arc::default(),
+        }
+    }
 }
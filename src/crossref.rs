@@ -0,0 +1,704 @@
+//! crossref.rs — Numbering and cross-references: equations (`\label{}`,
+//!               `\tag{}`, `\notag`; `\eqref{}` / `\ref{}` in the text) and
+//!               captioned figures and tables (`@fig:id` / `@tbl:id`).
+//!
+//! Equations are handled on the markdown after math extraction, so every
+//! display equation is a [`MathExpr`] and its position is the placeholder's.
+//! Numbers are added with `\tag{}`, which KaTeX typesets on both render paths.
+//! Figures and tables are handled on the parsed markdown events.
+
+use crate::config::EquationNumbering;
+use crate::renderer::{escape_html, find_code_regions, is_chinese, markdown_options, MathExpr, MathKind};
+use pulldown_cmark::{html, CowStr, Event, Parser, Tag};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// A label's anchor and number (without parentheses).
+#[derive(Debug, Clone)]
+struct Target {
+    id: String,
+    number: String,
+}
+
+/// Levels and byte offsets of the headings in `text`.
+fn heading_offsets(text: &str) -> Vec<(usize, usize)> {
+    Parser::new_ext(text, markdown_options())
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Heading(level, ..)) => Some((level as usize, range.start)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn top_level(headings: &[(usize, usize)]) -> Option<usize> {
+    let count = |level: usize| headings.iter().filter(|(l, _)| *l == level).count();
+    (1..=6)
+        .find(|&l| count(l) > 1)
+        .or_else(|| headings.iter().map(|(l, _)| *l).min())
+}
+
+/// Level of the headings that divide `text` into sections: the highest level
+/// used more than once (usually `#`, or `##` below a single title heading).
+pub(crate) fn section_level(text: &str) -> Option<usize> {
+    top_level(&heading_offsets(text))
+}
+
+/// Byte offsets of the section headings (see [`section_level`]), for
+/// [`EquationNumbering::Section`].
+fn section_starts(text: &str) -> Vec<usize> {
+    let headings = heading_offsets(text);
+    let Some(top) = top_level(&headings) else {
+        return Vec::new();
+    };
+    headings.into_iter().filter(|(l, _)| *l == top).map(|(_, start)| start).collect()
+}
+
+/// Number the display equations in `exprs` and strip their `\label{}`s and
+/// `\notag`s.  Returns the labels and, per numbered expression placeholder,
+/// the anchor id of its block.
+fn number_equations(
+    text: &str,
+    exprs: &mut [MathExpr],
+    mode: EquationNumbering,
+) -> (HashMap<String, Target>, HashMap<String, String>) {
+    let label_re = Regex::new(r"\\label\s*\{([^}]*)\}").unwrap();
+    let tag_re = Regex::new(r"\\tag\*?\s*\{([^}]*)\}").unwrap();
+    let notag_re = Regex::new(r"\\(?:notag|nonumber)\b").unwrap();
+
+    let mut blocks: Vec<(usize, usize)> = exprs
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| matches!(e.kind, MathKind::Block))
+        .filter_map(|(i, e)| Some((text.find(&e.placeholder)?, i)))
+        .collect();
+    blocks.sort();
+    let sections = if mode == EquationNumbering::Section { section_starts(text) } else { Vec::new() };
+
+    let mut labels: HashMap<String, Target> = HashMap::new();
+    let mut anchors: HashMap<String, String> = HashMap::new();
+    let mut counter = 0;
+    let mut current_section = 0;
+    for (pos, i) in blocks {
+        let expr = &mut exprs[i];
+        let names: Vec<String> = label_re
+            .captures_iter(&expr.content)
+            .map(|c| c[1].trim().to_string())
+            .collect();
+        let notag = notag_re.is_match(&expr.content);
+        let mut content = label_re.replace_all(&expr.content, "").into_owned();
+        content = notag_re.replace_all(&content, "").trim().to_string();
+
+        let number = if let Some(tag) = tag_re.captures(&content) {
+            Some(tag[1].trim().to_string())
+        } else if notag || (mode == EquationNumbering::Labeled && names.is_empty()) {
+            None
+        } else {
+            let section = sections.iter().filter(|&&s| s < pos).count();
+            if section != current_section {
+                current_section = section;
+                counter = 0;
+            }
+            counter += 1;
+            // Equations before the first section heading get plain numbers.
+            let number = match mode {
+                EquationNumbering::Section if section > 0 => format!("{}.{}", section, counter),
+                _ => counter.to_string(),
+            };
+            content.push_str(&format!(" \\tag{{{}}}", number));
+            Some(number)
+        };
+        expr.content = content;
+
+        let Some(number) = number else {
+            for name in names {
+                eprintln!("Warning: equation label {} is on an unnumbered equation", name);
+            }
+            continue;
+        };
+        for name in names {
+            if labels.contains_key(&name) {
+                eprintln!("Warning: duplicate equation label: {}", name);
+                continue;
+            }
+            let id = anchors.entry(expr.placeholder.clone()).or_insert_with(|| name.clone()).clone();
+            labels.insert(name, Target { id, number: number.clone() });
+        }
+    }
+    (labels, anchors)
+}
+
+/// Anchor id and reference text of `name`: `(3)` for `\eqref`, `3` for
+/// `\ref`; no anchor and `??` when the label is unknown.
+fn reference<'a>(labels: &'a HashMap<String, Target>, name: &str, paren: bool) -> (Option<&'a str>, String) {
+    let target = labels.get(name);
+    if target.is_none() {
+        eprintln!("Warning: unresolved equation reference: {}", name);
+    }
+    let number = target.map_or("??", |t| t.number.as_str());
+    let text = if paren { format!("({})", number) } else { number.to_string() };
+    (target.map(|t| t.id.as_str()), text)
+}
+
+fn reference_link(labels: &HashMap<String, Target>, name: &str, paren: bool) -> String {
+    match reference(labels, name, paren) {
+        (Some(id), text) => format!(
+            r##"<a class="eqref" href="#{}">{}</a>"##,
+            escape_html(id),
+            escape_html(&text)
+        ),
+        (None, text) => text,
+    }
+}
+
+/// Number display equations (see [`EquationNumbering`]) and turn `\eqref{}` /
+/// `\ref{}` into links to them.  `text` is the markdown with math replaced
+/// by placeholders; returns the new text and, per numbered expression
+/// placeholder, the anchor id its block should carry.
+///
+/// References outside code become links; inside math they become plain
+/// `\text{}`, except that math consisting of a single reference (`$\eqref{x}$`)
+/// is replaced by the link and dropped from `exprs`, so it is never rendered.
+/// Unresolved labels are reported and shown as `??`.
+pub(crate) fn resolve_equations(
+    text: &str,
+    exprs: &mut Vec<MathExpr>,
+    mode: EquationNumbering,
+) -> (String, HashMap<String, String>) {
+    let (labels, anchors) = number_equations(text, exprs, mode);
+    let ref_re = Regex::new(r"\\(eq)?ref\s*\{([^}]*)\}").unwrap();
+    let lone_ref = Regex::new(r"^\\(eq)?ref\s*\{([^}]*)\}$").unwrap();
+
+    let mut text = text.to_string();
+    exprs.retain(|expr| match (&expr.kind, lone_ref.captures(&expr.content)) {
+        (MathKind::Inline, Some(cap)) => {
+            let link = reference_link(&labels, cap[2].trim(), cap.get(1).is_some());
+            text = text.replace(&expr.placeholder, &link);
+            false
+        }
+        _ => true,
+    });
+    for expr in exprs.iter_mut() {
+        if ref_re.is_match(&expr.content) {
+            expr.content = ref_re
+                .replace_all(&expr.content, |cap: &regex::Captures| {
+                    let (_, number) = reference(&labels, cap[2].trim(), cap.get(1).is_some());
+                    format!("\\text{{{}}}", number)
+                })
+                .into_owned();
+        }
+    }
+
+    let code = find_code_regions(&text);
+    let text = ref_re
+        .replace_all(&text, |cap: &regex::Captures| {
+            let start = cap.get(0).map_or(0, |m| m.start());
+            if code.iter().any(|r| r.contains(&start)) {
+                cap[0].to_string()
+            } else {
+                reference_link(&labels, cap[2].trim(), cap.get(1).is_some())
+            }
+        })
+        .into_owned();
+    (text, anchors)
+}
+
+// ─────────────────────────────────────────────
+//  Figures and tables
+// ─────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FloatKind {
+    Figure,
+    Table,
+}
+
+impl FloatKind {
+    /// Caption prefix: "Figure" / "图", "Table" / "表".
+    fn name(self, chinese: bool) -> &'static str {
+        match (self, chinese) {
+            (Self::Figure, false) => "Figure",
+            (Self::Figure, true) => "图",
+            (Self::Table, false) => "Table",
+            (Self::Table, true) => "表",
+        }
+    }
+}
+
+/// Caption line prefixes (ASCII ones match case-insensitively).
+const CAPTION_PREFIXES: [(&str, FloatKind); 6] = [
+    ("Figure:", FloatKind::Figure),
+    ("图:",     FloatKind::Figure),
+    ("图：",    FloatKind::Figure),
+    ("Table:",  FloatKind::Table),
+    ("表:",     FloatKind::Table),
+    ("表：",    FloatKind::Table),
+];
+
+/// A numbered figure or table, as listed in the list of figures / tables.
+#[derive(Debug, Clone)]
+pub(crate) struct Float {
+    pub kind: FloatKind,
+    pub id: String,
+    /// "Figure 3" / "图 3".
+    pub label: String,
+    /// Inline HTML of the caption, without links and footnote references.
+    pub caption: String,
+}
+
+/// The figures and tables of a document and the language of their labels.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Floats {
+    pub list: Vec<Float>,
+    pub chinese: bool,
+}
+
+/// What goes between a label and its caption: "Figure 1: Caption", "图 1 标题".
+pub(crate) fn caption_separator(chinese: bool, caption: &str) -> &'static str {
+    match (caption.trim().is_empty(), chinese) {
+        (true, _) => "",
+        (false, true) => " ",
+        (false, false) => ": ",
+    }
+}
+
+/// A captioned figure or table found in the event stream.
+struct Found<'a> {
+    kind: FloatKind,
+    /// Events replaced by the figure: the element and its caption paragraph.
+    range: std::ops::Range<usize>,
+    /// The image (figures) or the table events.
+    body: Vec<Event<'a>>,
+    id: Option<String>,
+    caption: Vec<Event<'a>>,
+}
+
+fn attribute_re() -> Regex {
+    Regex::new(r"\s*\{#([^}\s]+)\}\s*$").unwrap()
+}
+
+/// Inline events of a caption or an image.
+type Inline<'a> = Vec<Event<'a>>;
+
+/// A caption line's kind, `{#id}` and caption events.
+type CaptionLine<'a> = (FloatKind, Option<String>, Inline<'a>);
+
+/// Strip a trailing `{#id}` off the last text event and return the id.
+fn take_attribute(events: &mut Vec<Event>) -> Option<String> {
+    let Some(Event::Text(last)) = events.last() else {
+        return None;
+    };
+    let cap = attribute_re().captures(last)?;
+    let id = cap[1].to_string();
+    let rest = last[..cap.get(0).map_or(0, |m| m.start())].to_string();
+    if rest.is_empty() {
+        events.pop();
+    } else if let Some(last) = events.last_mut() {
+        *last = Event::Text(rest.into());
+    }
+    Some(id)
+}
+
+/// A caption line: inline events starting with `Figure:` / `Table:` (or
+/// `图：` / `表：`), optionally ending in `{#id}`.
+fn caption_line<'a>(inline: &[Event<'a>]) -> Option<CaptionLine<'a>> {
+    let Some(Event::Text(first)) = inline.first() else {
+        return None;
+    };
+    let (kind, rest) = CAPTION_PREFIXES.iter().find_map(|(prefix, kind)| {
+        let head = first.get(..prefix.len())?;
+        head.eq_ignore_ascii_case(prefix).then(|| (*kind, &first[prefix.len()..]))
+    })?;
+    let mut events = vec![Event::Text(CowStr::from(rest.trim_start().to_string()))];
+    events.extend(inline[1..].iter().cloned());
+    let id = take_attribute(&mut events);
+    Some((kind, id, events))
+}
+
+/// A paragraph holding only an image, optionally followed by `{#id}` and a
+/// caption line on the next line.  Returns the image events, the id and the
+/// caption line's events.
+fn image_paragraph<'a>(inline: &[Event<'a>]) -> Option<(Inline<'a>, Option<String>, Option<Inline<'a>>)> {
+    if !matches!(inline.first(), Some(Event::Start(Tag::Image(..)))) {
+        return None;
+    }
+    let end = inline.iter().position(|e| matches!(e, Event::End(Tag::Image(..))))?;
+    let image = inline[..=end].to_vec();
+    let mut rest = &inline[end + 1..];
+    let mut id = None;
+    if let Some(Event::Text(text)) = rest.first() {
+        match attribute_re().captures(text) {
+            Some(cap) if cap.get(0).is_some_and(|m| text[..m.start()].trim().is_empty()) => {
+                id = Some(cap[1].to_string())
+            }
+            _ if text.trim().is_empty() => {}
+            _ => return None,
+        }
+        rest = &rest[1..];
+    }
+    let caption = match rest.split_first() {
+        None => None,
+        Some((Event::SoftBreak | Event::HardBreak, line)) => match caption_line(line)? {
+            (FloatKind::Figure, line_id, events) => {
+                id = id.or(line_id);
+                Some(events)
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((image, id, caption))
+}
+
+/// Index of the `End` matching the `Start` at `start`.
+fn block_end(events: &[Event], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, event) in events.iter().enumerate().skip(start) {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    events.len() - 1
+}
+
+/// The caption line of the paragraph starting at `start`, if it is one, and
+/// the index after the paragraph.
+fn caption_paragraph<'a>(
+    events: &[Event<'a>],
+    start: usize,
+) -> Option<(CaptionLine<'a>, usize)> {
+    if !matches!(events.get(start), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+    let end = block_end(events, start);
+    Some((caption_line(&events[start + 1..end])?, end + 1))
+}
+
+/// Captioned figures and tables, in document order.  A figure is an image
+/// alone in its paragraph with an `{#id}` or a `Figure:` line (on the next
+/// line or as the next paragraph).  A table has a `Table:` paragraph right
+/// before it or, failing that, right after it.
+fn find_floats<'a>(events: &[Event<'a>]) -> Vec<Found<'a>> {
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < events.len() {
+        match &events[i] {
+            Event::Start(Tag::Paragraph) => {
+                let end = block_end(events, i);
+                let inline = &events[i + 1..end];
+                if let Some((image, mut id, mut caption)) = image_paragraph(inline) {
+                    let mut stop = end + 1;
+                    if caption.is_none() {
+                        if let Some(((FloatKind::Figure, line_id, events), next)) = caption_paragraph(events, end + 1) {
+                            id = id.or(line_id);
+                            caption = Some(events);
+                            stop = next;
+                        }
+                    }
+                    if caption.is_some() || id.is_some() {
+                        // Without a caption line the alt text is the caption.
+                        let caption = caption.unwrap_or_else(|| image[1..image.len() - 1].to_vec());
+                        found.push(Found { kind: FloatKind::Figure, range: i..stop, body: image, id, caption });
+                    }
+                    i = stop;
+                    continue;
+                }
+                if let (Some((FloatKind::Table, id, caption)), Some(Event::Start(Tag::Table(_)))) =
+                    (caption_line(inline), events.get(end + 1))
+                {
+                    let table_end = block_end(events, end + 1);
+                    let body = events[end + 1..=table_end].to_vec();
+                    found.push(Found { kind: FloatKind::Table, range: i..table_end + 1, body, id, caption });
+                    i = table_end + 1;
+                    continue;
+                }
+                i = end + 1;
+            }
+            Event::Start(Tag::Table(_)) => {
+                let end = block_end(events, i);
+                if let Some(((FloatKind::Table, id, caption), next)) = caption_paragraph(events, end + 1) {
+                    // A caption between two tables belongs to the second.
+                    if !matches!(events.get(next), Some(Event::Start(Tag::Table(_)))) {
+                        let body = events[i..=end].to_vec();
+                        found.push(Found { kind: FloatKind::Table, range: i..next, body, id, caption });
+                        i = next;
+                        continue;
+                    }
+                }
+                i = end + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    found
+}
+
+/// Merge runs of text events; the parser splits text at possible delimiters.
+fn merge_text(events: Vec<Event>) -> Vec<Event> {
+    let mut out: Vec<Event> = Vec::with_capacity(events.len());
+    for event in events {
+        match (out.last_mut(), event) {
+            (Some(Event::Text(prev)), Event::Text(text)) => {
+                *prev = CowStr::from(format!("{}{}", prev, text));
+            }
+            (_, event) => out.push(event),
+        }
+    }
+    out
+}
+
+/// `text` with `@fig:id` / `@tbl:id` references turned into links (or just
+/// their labels), as HTML; `None` when it has no references.
+fn link_float_refs(text: &str, targets: &HashMap<String, String>, chinese: bool, links: bool) -> Option<String> {
+    let re = Regex::new(r"@((fig|tbl):[\w:.\-]*\w)").unwrap();
+    let mut out = String::new();
+    let mut last = 0;
+    for cap in re.captures_iter(text) {
+        let m = cap.get(0).unwrap();
+        // Not part of an e-mail address.
+        if text[..m.start()].chars().next_back().is_some_and(char::is_alphanumeric) {
+            continue;
+        }
+        out.push_str(&escape_html(&text[last..m.start()]));
+        match targets.get(&cap[1]) {
+            Some(label) if !links => out.push_str(&escape_html(label)),
+            Some(label) => out.push_str(&format!(
+                r##"<a class="figref" href="#{}">{}</a>"##,
+                escape_html(&cap[1]),
+                escape_html(label)
+            )),
+            None => {
+                let kind = if &cap[2] == "fig" { FloatKind::Figure } else { FloatKind::Table };
+                if links {
+                    eprintln!("Warning: unresolved {} reference: @{}", kind.name(false).to_lowercase(), &cap[1]);
+                }
+                out.push_str(&format!("{} ??", kind.name(chinese)));
+            }
+        }
+        last = m.end();
+    }
+    (last > 0).then(|| out + &escape_html(&text[last..]))
+}
+
+/// Replace reference-bearing text events (outside image alt text) with HTML.
+fn link_refs<'a>(
+    events: Vec<Event<'a>>,
+    targets: &HashMap<String, String>,
+    chinese: bool,
+    links: bool,
+) -> Vec<Event<'a>> {
+    let mut in_image = 0;
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::Start(Tag::Image(..)) => {
+                in_image += 1;
+                event
+            }
+            Event::End(Tag::Image(..)) => {
+                in_image -= 1;
+                event
+            }
+            Event::Text(text) if in_image == 0 => match link_float_refs(&text, targets, chinese, links) {
+                Some(html) => Event::Html(html.into()),
+                None => Event::Text(text),
+            },
+            other => other,
+        })
+        .collect()
+}
+
+fn events_html<'a>(events: impl Iterator<Item = Event<'a>>) -> String {
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+/// Wrap captioned figures in `<figure>` and captioned tables in
+/// `<figure class="table-figure">`, numbered separately ("Figure 1", "Table
+/// 1"; "图 1", "表 1" in Chinese documents), and turn `@fig:id` / `@tbl:id`
+/// in the text into links to them.
+///
+/// The label language follows `lang`; when unset it is guessed from
+/// `sample` (the heading text) and the captions.  Figures without an `{#id}`
+/// get `figure-N` / `table-N`, for the lists of figures and tables.
+pub(crate) fn number_floats<'a>(events: Vec<Event<'a>>, lang: Option<&str>, sample: &str) -> (Vec<Event<'a>>, Floats) {
+    let events = merge_text(events);
+    let found = find_floats(&events);
+    if found.is_empty() && !events.iter().any(|e| matches!(e, Event::Text(t) if t.contains("@fig:") || t.contains("@tbl:"))) {
+        return (events, Floats::default());
+    }
+
+    let caption_text: String = found
+        .iter()
+        .flat_map(|f| f.caption.iter())
+        .filter_map(|e| match e {
+            Event::Text(t) => Some(t.as_ref()),
+            _ => None,
+        })
+        .collect();
+    let chinese = is_chinese(lang, &format!("{}{}", sample, caption_text));
+
+    let mut targets: HashMap<String, String> = HashMap::new();
+    let mut list = Vec::new();
+    let (mut figures, mut tables) = (0, 0);
+    for f in &found {
+        let counter = if f.kind == FloatKind::Figure { &mut figures } else { &mut tables };
+        *counter += 1;
+        let label = format!("{} {}", f.kind.name(chinese), counter);
+        let id = match &f.id {
+            Some(id) if !targets.contains_key(id) => id.clone(),
+            duplicate => {
+                if let Some(id) = duplicate {
+                    eprintln!("Warning: duplicate figure or table id: {}", id);
+                }
+                let kind = if f.kind == FloatKind::Figure { "figure" } else { "table" };
+                format!("{}-{}", kind, counter)
+            }
+        };
+        targets.insert(id.clone(), label.clone());
+        list.push(Float { kind: f.kind, id, label, caption: String::new() });
+    }
+    for (f, float) in found.iter().zip(&mut list) {
+        let caption = link_refs(f.caption.clone(), &targets, chinese, false);
+        float.caption = events_html(caption.into_iter().filter(|e| {
+            !matches!(e, Event::Start(Tag::Link(..)) | Event::End(Tag::Link(..)) | Event::FootnoteReference(_))
+        }));
+    }
+
+    let mut out = Vec::with_capacity(events.len());
+    let mut found = found.into_iter().zip(&list).peekable();
+    let mut events = events.into_iter().enumerate();
+    while let Some((i, event)) = events.next() {
+        let Some((f, float)) = found.next_if(|(f, _)| f.range.start == i) else {
+            out.push(event);
+            continue;
+        };
+        for _ in f.range.start + 1..f.range.end {
+            events.next();
+        }
+        let caption = events_html(link_refs(f.caption, &targets, chinese, true).into_iter());
+        let figcaption = format!(
+            r#"<figcaption><span class="caption-label">{}</span>{}{}</figcaption>"#,
+            escape_html(&float.label),
+            caption_separator(chinese, &caption),
+            caption.trim()
+        );
+        let id = escape_html(&float.id);
+        match f.kind {
+            FloatKind::Figure => {
+                out.push(Event::Html(format!("<figure class=\"figure\" id=\"{}\">\n", id).into()));
+                out.extend(f.body);
+                out.push(Event::Html(format!("\n{}\n</figure>\n", figcaption).into()));
+            }
+            FloatKind::Table => {
+                out.push(Event::Html(
+                    format!("<figure class=\"table-figure\" id=\"{}\">\n{}\n", id, figcaption).into(),
+                ));
+                out.extend(f.body);
+                out.push(Event::Html("</figure>\n".into()));
+            }
+        }
+    }
+    (link_refs(out, &targets, chinese, true), Floats { list, chinese })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::process_math_expressions;
+
+    fn equations(markdown: &str, mode: EquationNumbering) -> (String, Vec<MathExpr>, HashMap<String, String>) {
+        let (text, mut exprs) = process_math_expressions(markdown);
+        let (text, anchors) = resolve_equations(&text, &mut exprs, mode);
+        (text, exprs, anchors)
+    }
+
+    #[test]
+    fn numbers_labeled_equations_and_links_references() {
+        let md = "$$a \\label{first}$$\n\n$$b$$\n\n$$c \\label{third}$$\n\nSee \\eqref{third} and \\ref{first}.\n";
+        let (text, exprs, anchors) = equations(md, EquationNumbering::Labeled);
+        assert_eq!(exprs[0].content, "a \\tag{1}");
+        assert_eq!(exprs[1].content, "b");
+        assert_eq!(exprs[2].content, "c \\tag{2}");
+        assert_eq!(anchors[&exprs[2].placeholder], "third");
+        assert!(text.contains(r##"See <a class="eqref" href="#third">(2)</a> and <a class="eqref" href="#first">1</a>."##));
+    }
+
+    #[test]
+    fn tag_and_notag_override_numbering() {
+        let md = "$$a \\tag{*}$$\n\n$$b \\notag$$\n\n$$c$$\n";
+        let (_, exprs, _) = equations(md, EquationNumbering::All);
+        assert_eq!(exprs[0].content, "a \\tag{*}");
+        assert_eq!(exprs[1].content, "b");
+        assert_eq!(exprs[2].content, "c \\tag{1}");
+    }
+
+    #[test]
+    fn section_numbering_restarts_per_section() {
+        let md = "$$z$$\n\n# One\n\n$$a$$\n\n$$b$$\n\n# Two\n\n$$c$$\n";
+        let (_, exprs, _) = equations(md, EquationNumbering::Section);
+        let tags: Vec<&str> = exprs.iter().map(|e| e.content.as_str()).collect();
+        assert_eq!(tags, ["z \\tag{1}", "a \\tag{1.1}", "b \\tag{1.2}", "c \\tag{2.1}"]);
+    }
+
+    #[test]
+    fn reference_only_math_becomes_a_link_and_is_not_rendered() {
+        let md = "$$x \\label{e}$$\n\nBy $\\eqref{e}$ and $y = \\ref{e}$.\n";
+        let (text, exprs, _) = equations(md, EquationNumbering::Labeled);
+        assert_eq!(exprs.len(), 2);
+        assert!(text.contains(r##"By <a class="eqref" href="#e">(1)</a>"##));
+        assert_eq!(exprs[1].content, "y = \\text{1}");
+    }
+
+    #[test]
+    fn unresolved_and_code_references() {
+        let md = "\\eqref{missing} and `\\ref{kept}`\n";
+        let (text, _, _) = equations(md, EquationNumbering::Labeled);
+        assert_eq!(text, "(??) and `\\ref{kept}`\n");
+    }
+
+    fn floats(markdown: &str, lang: Option<&str>) -> (String, Floats) {
+        let events: Vec<Event> = Parser::new_ext(markdown, markdown_options()).collect();
+        let (events, floats) = number_floats(events, lang, "");
+        (events_html(events.into_iter()), floats)
+    }
+
+    #[test]
+    fn numbers_figures_with_ids_and_caption_lines() {
+        let md = "![Plot](a.png){#fig:plot}\n\n![b](b.png)\nFigure: Second one\n\nSee @fig:plot and @fig:missing.\n";
+        let (html, floats) = floats(md, None);
+        let labels: Vec<(&str, &str)> = floats.list.iter().map(|f| (f.id.as_str(), f.label.as_str())).collect();
+        assert_eq!(labels, [("fig:plot", "Figure 1"), ("figure-2", "Figure 2")]);
+        assert_eq!(floats.list[0].caption, "Plot");
+        assert!(html.contains(r#"<figure class="figure" id="fig:plot">"#));
+        assert!(html.contains(r#"<figcaption><span class="caption-label">Figure 2</span>: Second one</figcaption>"#));
+        assert!(html.contains(r##"See <a class="figref" href="#fig:plot">Figure 1</a> and Figure ??."##));
+    }
+
+    #[test]
+    fn table_captions_before_or_after_the_table() {
+        let md = "Table: Before {#tbl:a}\n\n| x |\n|---|\n| 1 |\n\n| y |\n|---|\n| 2 |\n\n表：之后\n\n见 @tbl:a。\n";
+        let (html, floats) = floats(md, Some("zh"));
+        let labels: Vec<(&str, &str)> = floats.list.iter().map(|f| (f.id.as_str(), f.label.as_str())).collect();
+        assert_eq!(labels, [("tbl:a", "表 1"), ("table-2", "表 2")]);
+        assert!(floats.chinese);
+        assert!(html.contains(r#"<figcaption><span class="caption-label">表 2</span> 之后</figcaption>"#));
+        assert!(html.contains(r##"见 <a class="figref" href="#tbl:a">表 1</a>。"##));
+    }
+
+    #[test]
+    fn plain_images_are_not_figures() {
+        let (html, floats) = floats("![alt](a.png)\n\ntext\n", None);
+        assert!(floats.list.is_empty());
+        assert!(!html.contains("<figure"));
+    }
+
+}
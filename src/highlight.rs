@@ -0,0 +1,106 @@
+//! highlight.rs — Render-time syntax highlighting of fenced code blocks
+//!                (syntect with bat's extra syntaxes and themes).
+//!
+//! Code is marked up with `hl-*` classes only; the colours come from the
+//! theme stylesheet, so the same HTML works with every theme.
+
+use crate::renderer::escape_html;
+use std::sync::OnceLock;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use two_face::theme::EmbeddedThemeName;
+
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Built-in code themes (`--code-theme`) → bundled theme.  `github` is the
+/// default and prints well; `none` leaves code uncoloured.
+const THEMES: [(&str, EmbeddedThemeName); 10] = [
+    ("github",          EmbeddedThemeName::InspiredGithub),
+    ("one-light",       EmbeddedThemeName::OneHalfLight),
+    ("one-dark",        EmbeddedThemeName::OneHalfDark),
+    ("solarized-light", EmbeddedThemeName::SolarizedLight),
+    ("solarized-dark",  EmbeddedThemeName::SolarizedDark),
+    ("gruvbox-light",   EmbeddedThemeName::GruvboxLight),
+    ("gruvbox-dark",    EmbeddedThemeName::GruvboxDark),
+    ("monokai",         EmbeddedThemeName::MonokaiExtended),
+    ("dracula",         EmbeddedThemeName::Dracula),
+    ("nord",            EmbeddedThemeName::Nord),
+];
+
+/// Names accepted by [`theme_css`].
+pub fn theme_names() -> Vec<&'static str> {
+    THEMES.iter().map(|(name, _)| *name).chain(["none"]).collect()
+}
+
+/// Stylesheet colouring highlighted code with the named theme, `None` for an
+/// unknown name.
+pub fn theme_css(name: &str) -> Option<String> {
+    if name == "none" {
+        return Some(String::new());
+    }
+    let (_, theme) = THEMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name))?;
+    css_for_theme_with_class_style(two_face::theme::extra().get(*theme), CLASS_STYLE).ok()
+}
+
+/// Syntax definitions, loaded on first use.
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(two_face::syntax::extra_newlines)
+}
+
+/// Split highlighted HTML into lines, closing the spans still open at the end
+/// of each line and reopening them on the next, so every line stands alone.
+fn balanced_lines(html: &str) -> Vec<String> {
+    let mut open: Vec<&str> = Vec::new();
+    let mut lines = Vec::new();
+    for line in html.split('\n') {
+        let mut out: String = open.concat();
+        let mut rest = line;
+        while let Some(pos) = rest.find('<') {
+            let end = rest[pos..].find('>').map_or(rest.len(), |e| pos + e + 1);
+            let tag = &rest[pos..end];
+            if tag.starts_with("</") {
+                open.pop();
+            } else {
+                open.push(tag);
+            }
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+        out.push_str(rest);
+        out.push_str(&"</span>".repeat(open.len()));
+        lines.push(out);
+    }
+    lines
+}
+
+/// Highlighted HTML of a code block's contents, or escaped plain text when
+/// `lang` is empty or unknown.  With `line_numbers` every line is wrapped in
+/// `<span class="code-line">` for the numbered gutter.
+pub fn highlight_code(code: &str, lang: &str, line_numbers: bool) -> String {
+    let syntaxes = syntax_set();
+    let highlighted = syntaxes
+        .find_syntax_by_token(lang)
+        .filter(|_| !lang.is_empty())
+        .and_then(|syntax| {
+            let mut generator =
+                ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, CLASS_STYLE);
+            for line in LinesWithEndings::from(code) {
+                generator.parse_html_for_line_which_includes_newline(line).ok()?;
+            }
+            Some(generator.finalize())
+        })
+        .unwrap_or_else(|| escape_html(code));
+
+    if !line_numbers {
+        return highlighted;
+    }
+    // The spans closed after the final newline would make an extra line.
+    let mut lines = balanced_lines(&highlighted);
+    lines.truncate(code.lines().count().max(1));
+    lines
+        .into_iter()
+        .map(|line| format!("<span class=\"code-line\">{}</span>\n", line))
+        .collect()
+}
@@ -1,5 +1,5 @@
-/// katex_assets.rs — load KaTeX CSS (with inlined fonts), JS, and auto-render JS.
-///                    Mirrors katex-assets.js.
+//! katex_assets.rs — load KaTeX CSS (with inlined fonts), JS, and auto-render JS.
+//!                    Mirrors katex-assets.js.
 
 use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
 use regex::Regex;
@@ -105,3 +105,26 @@ pub fn get_local_katex_auto_render_js(assets_dir: &Path) -> String {
         }
     }
 }
+
+// ─────────────────────────────────────────────
+//  Bundled assets
+// ─────────────────────────────────────────────
+
+/// All KaTeX resources needed by `generate_html_document`, loaded once.
+#[derive(Debug, Clone, Default)]
+pub struct KatexAssets {
+    pub css: String,
+    pub js: String,
+    pub auto_render_js: String,
+}
+
+impl KatexAssets {
+    /// Load CSS (with inlined fonts), JS and auto-render JS from `assets_dir`.
+    pub fn load(assets_dir: &Path) -> Self {
+        Self {
+            css:            get_local_katex_css_with_inline_fonts(assets_dir),
+            js:             get_local_katex_js(assets_dir),
+            auto_render_js: get_local_katex_auto_render_js(assets_dir),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! katex_engine.rs — Server-side KaTeX typesetting.
+//!                   Runs the local `katex.min.js` inside an embedded QuickJS
+//!                   runtime so math becomes static HTML + MathML at render time.
+
+use rquickjs::{Context, Function, Runtime};
+
+/// Global the render function is stored under.
+const RENDER_FN: &str = "md2pdfRenderToString";
+
+/// A QuickJS context with KaTeX loaded and `renderToString` ready to call.
+/// QuickJS serialises access through the runtime lock, so one engine can be
+/// shared between threads.
+pub struct KatexEngine {
+    // Field order matters: the context must drop before the runtime.
+    context: Context,
+    _runtime: Runtime,
+}
+
+impl KatexEngine {
+    /// Evaluate `katex_js` (the contents of `katex.min.js`) in a fresh runtime.
+    pub fn new(katex_js: &str) -> Result<Self, String> {
+        if katex_js.is_empty() {
+            return Err("katex.min.js is not available".to_string());
+        }
+        let runtime = Runtime::new().map_err(|e| e.to_string())?;
+        let context = Context::full(&runtime).map_err(|e| e.to_string())?;
+        context.with(|ctx| {
+            ctx.eval::<(), _>(katex_js)
+                .map_err(|e| format!("cannot evaluate katex.min.js: {}", e))?;
+            let f: Function = ctx
+                .eval(
+                    r#"(function (tex, displayMode) {
+                        return katex.renderToString(tex, {
+                            displayMode: displayMode,
+                            throwOnError: false
+                        });
+                    })"#,
+                )
+                .map_err(|e| e.to_string())?;
+            ctx.globals().set(RENDER_FN, f).map_err(|e| e.to_string())
+        })?;
+
+        Ok(Self {
+            context,
+            _runtime: runtime,
+        })
+    }
+
+    /// Typeset one expression.  Parse errors are rendered inline by KaTeX
+    /// (`throwOnError: false`), exactly like the client-side auto-render.
+    pub fn render(&self, tex: &str, display: bool) -> Result<String, String> {
+        self.context.with(|ctx| {
+            let f: Function = ctx.globals().get(RENDER_FN).map_err(|e| e.to_string())?;
+            f.call((tex, display)).map_err(|e| e.to_string())
+        })
+    }
+}
@@ -0,0 +1,34 @@
+//! md2pdf-rs — Markdown (with LaTeX math) to HTML / PDF conversion library.
+//!
+//! The [`Converter`] owns the loaded KaTeX assets and all style / PDF
+//! options, so one instance can serve many conversions:
+//!
+//! ```no_run
+//! use md2pdf_rs::{Converter, PdfOptions, StyleOptions};
+//!
+//! let converter = Converter::builder()
+//!     .style_options(StyleOptions::default())
+//!     .pdf_options(PdfOptions::default())
+//!     .build();
+//!
+//! let html = converter.to_html("# Hello\n\n$e^{i\\pi} + 1 = 0$", "Hello");
+//! let pdf: Vec<u8> = converter.to_pdf("# Hello", "Hello")?;
+//! # Ok::<(), md2pdf_rs::AppError>(())
+//! ```
+
+mod admonition;
+pub mod bibliography;
+pub mod config;
+pub mod converter;
+mod crossref;
+pub mod highlight;
+pub mod katex_assets;
+pub mod katex_engine;
+pub mod pdf;
+pub mod renderer;
+pub mod template;
+pub mod theme;
+
+pub use config::{CitationStyle, CoverOptions, EquationNumbering, MathRender, OutputFormat, PdfOptions, RenderOptions, StyleOptions};
+pub use converter::{AppError, Converter, ConverterBuilder, Document};
+pub use renderer::FrontMatter;
@@ -1,152 +1,581 @@
-﻿mod cli;
-mod config;
-mod converter;
-mod katex_assets;
-mod renderer;
-mod template;
-
-use clap::Parser as ClapParser;
+﻿/// `println!` for status messages (see [`STDOUT_IS_DOCUMENT`]).  Defined
+/// before the modules so batch and watch progress follow it too.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::STDOUT_IS_DOCUMENT.load(std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+mod batch;
+mod cli;
+mod config_file;
+mod watch;
+
+use clap::{CommandFactory, FromArgMatches};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use config::{margin_to_inches, normalize_with_unit, resolve_assets_dir, PdfOptions, StyleOptions};
-use converter::generate_pdf;
-use katex_assets::{
-    get_local_katex_auto_render_js, get_local_katex_css_with_inline_fonts, get_local_katex_js,
+use md2pdf_rs::config::{
+    byte_size, cover_layout, header_footer_template, length_to_inches, normalize_with_unit, paper_size, CoverOptions, Margins,
+    CitationStyle, EquationNumbering, MathRender, OutputFormat, PdfOptions, RenderOptions, StyleOptions,
 };
-use renderer::render;
-use template::generate_html_document;
+use md2pdf_rs::template::COVER_LAYOUTS;
+use md2pdf_rs::Converter;
 
 // 
 //  Entry point
 // 
 
+/// Set when the document is written to stdout; status messages then go to
+/// stderr so they do not corrupt it.
+static STDOUT_IS_DOCUMENT: AtomicBool = AtomicBool::new(false);
+
 fn print_title() {
-    println!();
-    println!("");
-    println!("  Markdown LaTeX  PDF 转换器     ");
-    println!("  支持数学公式 | 美观排版          ");
-    println!("");
-    println!();
+    status!();
+    status!();
+    status!("  Markdown LaTeX  PDF 转换器     ");
+    status!("  支持数学公式 | 美观排版          ");
+    status!();
+    status!();
+}
+
+/// `-` in place of a path: stdin for the input, stdout for the output.
+fn is_std_stream(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Input / output path for the settings summary.
+fn describe(path: &Path, stream: &str) -> String {
+    if is_std_stream(path) {
+        stream.to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// Make `path` absolute against the current directory.
+fn absolutize(path: PathBuf) -> std::io::Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Ok(std::env::current_dir()?.join(path))
+    }
+}
+
+/// A style option for the settings summary; unset ones come from the theme.
+fn or_theme(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("主题默认")
+}
+
+/// Old `md2pdf INPUT OUTPUT` form: a second positional naming a .pdf/.html
+/// file is the output path.
+fn split_legacy_output(inputs: &mut Vec<PathBuf>, output: &mut Option<PathBuf>) {
+    if inputs.len() != 2 || output.is_some() || batch::is_glob(&inputs[1]) {
+        return;
+    }
+    let is_document = inputs[1]
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| OutputFormat::parse(&e.to_ascii_lowercase()).is_some());
+    if is_document {
+        *output = inputs.pop();
+    }
+}
+
+/// Parse the command line and fill in everything it leaves unset from the
+/// user and project `md2pdf.toml` files.  Returns the arguments and the config
+/// files that were applied.
+fn parse_args() -> Result<(cli::Args, Vec<PathBuf>), String> {
+    let argv: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let cmd = cli::Args::command();
+    let matches = cmd.clone().get_matches_from(argv.clone());
+    let cli_args = cli::Args::from_arg_matches(&matches).map_err(|e| e.to_string())?;
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    paths.extend(config_file::user_config_file());
+    match &cli_args.config {
+        Some(path) => paths.push(path.clone()),
+        None => {
+            let start = cli_args
+                .inputs
+                .first()
+                .filter(|p| !batch::is_glob(p))
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from("."));
+            paths.extend(config_file::project_config_file(&start));
+        }
+    }
+    paths.dedup();
+
+    let profile = cli_args.profile.as_deref();
+    let mut files = Vec::new();
+    let mut profile_found = false;
+    for path in &paths {
+        let (file, found) = config_file::ConfigFile::load(path, profile)?;
+        profile_found |= found;
+        files.push(file);
+    }
+    config_file::check_profile(profile, profile_found)?;
+
+    if files.is_empty() {
+        return Ok((cli_args, paths));
+    }
+    let merged = config_file::merged_args(&cmd, &matches, &files, argv)?;
+    let args = cli::Args::from_arg_matches(&cmd.get_matches_from(merged)).map_err(|e| e.to_string())?;
+    Ok((args, paths))
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut args, config_paths) = match parse_args() {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let from_stdin = args.inputs.iter().any(|p| is_std_stream(p));
+    let to_stdout = match &args.output {
+        Some(output) => is_std_stream(output),
+        None => from_stdin,
+    };
+    STDOUT_IS_DOCUMENT.store(to_stdout, Ordering::Relaxed);
     print_title();
 
-    let args = cli::Args::parse();
+    if args.inputs.is_empty() {
+        cli::Args::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "缺少输入文件 <INPUT> (可在命令行或配置文件的 `inputs` 中指定)",
+            )
+            .exit();
+    }
+    split_legacy_output(&mut args.inputs, &mut args.output);
+
+    let format = match OutputFormat::parse(&args.format) {
+        Some(f) => f,
+        None => {
+            eprintln!("不支持的格式: {}", args.format);
+            std::process::exit(1);
+        }
+    };
+
+    let math = match MathRender::parse(&args.math_render) {
+        Some(m) => m,
+        None => {
+            eprintln!("不支持的公式渲染方式: {}", args.math_render);
+            std::process::exit(1);
+        }
+    };
+
+    let equation_numbers = match EquationNumbering::parse(&args.equation_numbers) {
+        Some(n) => n,
+        None => {
+            eprintln!("不支持的公式编号方式: {} (可选: labeled, all, section)", args.equation_numbers);
+            std::process::exit(1);
+        }
+    };
+
+    let citation_style = match CitationStyle::parse(&args.citation_style) {
+        Some(s) => s,
+        None => {
+            eprintln!("不支持的引用格式: {} (可选: numeric, author-year, gb7714)", args.citation_style);
+            std::process::exit(1);
+        }
+    };
+    for path in &args.bibliography {
+        if let Err(e) = md2pdf_rs::bibliography::load_bibliography(path) {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if md2pdf_rs::highlight::theme_css(&args.code_theme).is_none() {
+        eprintln!(
+            "不支持的代码高亮主题: {} (可选: {})",
+            args.code_theme,
+            md2pdf_rs::highlight::theme_names().join(", ")
+        );
+        std::process::exit(1);
+    }
+
+    if let Err(e) = md2pdf_rs::theme::load_theme(&args.theme) {
+        eprintln!("错误: {}", e);
+        std::process::exit(1);
+    }
+
+    for path in &args.css {
+        if let Err(e) = fs::metadata(path) {
+            eprintln!("错误: 无法读取 CSS 文件 {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(path) = &args.template {
+        let checked = fs::read_to_string(path)
+            .map_err(|e| format!("无法读取 HTML 模板 {}: {}", path.display(), e))
+            .and_then(|t| md2pdf_rs::template::validate_html_template(&t));
+        if let Err(e) = checked {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let Some(embed_max_size) = byte_size(&args.embed_max_size) else {
+        eprintln!("无效的图片大小上限: {}", args.embed_max_size);
+        std::process::exit(1);
+    };
+    let cover_layout = args.cover.as_deref().and_then(cover_layout);
+    if let Some(layout) = &cover_layout {
+        if !COVER_LAYOUTS.contains(&layout.to_ascii_lowercase().as_str()) && !Path::new(layout).is_file() {
+            eprintln!(
+                "错误: 未知封面版式 {} (可选: {}，或封面 HTML 模板文件)",
+                layout,
+                COVER_LAYOUTS.join(", ")
+            );
+            std::process::exit(1);
+        }
+    }
+    if let Some(logo) = &args.logo {
+        if !logo.contains("://") && !Path::new(logo).is_file() {
+            eprintln!("错误: 找不到封面 Logo 图片: {}", logo);
+            std::process::exit(1);
+        }
+    }
 
     //  Validate input 
-    if !args.input.exists() {
-        eprintln!("错误: 输入文件不存在: {}", args.input.display());
+    let is_batch = args.inputs.len() > 1
+        || args.inputs.iter().any(|p| p.is_dir() || batch::is_glob(p));
+    if from_stdin && (is_batch || args.watch) {
+        eprintln!("错误: 标准输入 (-) 只能作为唯一的输入，且不支持监视模式");
         std::process::exit(1);
     }
+    if to_stdout && (is_batch || args.watch) {
+        eprintln!("错误: 输出到标准输出 (-) 只支持单个输入，且不支持监视模式");
+        std::process::exit(1);
+    }
+    let sources = if from_stdin {
+        Vec::new()
+    } else {
+        match batch::expand_inputs(&args.inputs) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                std::process::exit(1);
+            }
+        }
+    };
 
     //  Normalize numeric options 
     let margin            = normalize_with_unit(&args.margin, "mm");
-    let font_size         = normalize_with_unit(&args.font_size, "px");
-    let paragraph_spacing = normalize_with_unit(&args.paragraph_spacing, "em");
-    let math_spacing      = normalize_with_unit(&args.math_spacing, "px");
-
-    //  Determine output path 
-    let output_path: PathBuf = args.output.unwrap_or_else(|| {
-        let ext = if args.format == "html" { "html" } else { "pdf" };
-        args.input.with_extension(ext)
-    });
-    let output_path = if output_path.is_absolute() {
-        output_path
+    let font_size         = args.font_size.as_deref().map(|v| normalize_with_unit(v, "px"));
+    let paragraph_spacing = args.paragraph_spacing.as_deref().map(|v| normalize_with_unit(v, "em"));
+    let math_spacing      = args.math_spacing.as_deref().map(|v| normalize_with_unit(v, "px"));
+
+    let Some(mut margins) = Margins::parse(&margin) else {
+        eprintln!("无效的页边距: {}", args.margin);
+        std::process::exit(1);
+    };
+    let sides = [
+        (&args.margin_top, &mut margins.top),
+        (&args.margin_right, &mut margins.right),
+        (&args.margin_bottom, &mut margins.bottom),
+        (&args.margin_left, &mut margins.left),
+    ];
+    for (value, side) in sides {
+        if let Some(value) = value {
+            let Some(inches) = length_to_inches(&normalize_with_unit(value, "mm")) else {
+                eprintln!("无效的页边距: {}", value);
+                std::process::exit(1);
+            };
+            *side = inches;
+        }
+    }
+    let Some((paper_width, paper_height)) = paper_size(&args.paper) else {
+        eprintln!("不支持的纸张大小: {}", args.paper);
+        std::process::exit(1);
+    };
+    let mut templates = [None, None];
+    for (value, template) in [&args.header, &args.footer].into_iter().zip(&mut templates) {
+        if let Some(value) = value {
+            *template = header_footer_template(value).unwrap_or_else(|e| {
+                eprintln!("无法读取页眉/页脚模板 {}: {}", value, e);
+                std::process::exit(1);
+            });
+        }
+    }
+    let [header_template, footer_template] = templates;
+    if (header_template.is_some() && margins.top < 0.4)
+        || (footer_template.is_some() && margins.bottom < 0.4)
+    {
+        eprintln!("Warning: 页眉/页脚打印在页边距内，边距过小 (建议至少 10mm) 时可能显示不全");
+    }
+
+    //  Determine output path(s) 
+    let output = args.output.clone().filter(|o| !is_std_stream(o)).map(absolutize).transpose()?;
+    let jobs = if is_batch {
+        batch::plan_jobs(sources, output.as_deref(), format).unwrap_or_else(|e| {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        })
     } else {
-        std::env::current_dir()?.join(output_path)
+        let input = match sources.first() {
+            Some(source) => source.path.clone(),
+            None => PathBuf::from("-"),
+        };
+        let output = match output.clone() {
+            _ if to_stdout => PathBuf::from("-"),
+            Some(o) => o,
+            None => absolutize(input.with_extension(format.extension()))?,
+        };
+        vec![batch::Job { input, output }]
     };
 
     //  Print settings 
-    println!("开始转换...");
-    println!("  输入:     {}", args.input.display());
-    println!("  输出:     {}", output_path.display());
-    println!("  格式:     {}", args.format.to_uppercase());
-    println!("  字体大小: {}", font_size);
-    println!("  页边距:   {}", margin);
-    println!("  中文字体: {}", args.chinese_font);
-    println!("  文字厚度: {}", args.font_weight);
-    println!("  行间距:   {}", args.line_spacing);
-    println!("  段落间距: {}", paragraph_spacing);
-    println!("  公式间距: {}", math_spacing);
+    status!("开始转换...");
+    for path in &config_paths {
+        status!("  配置文件: {}", path.display());
+    }
+    if let Some(profile) = &args.profile {
+        status!("  配置组:   {}", profile);
+    }
+    if is_batch {
+        status!("  输入:     {} 个文件", jobs.len());
+        match &output {
+            Some(dir) => status!("  输出目录: {}", dir.display()),
+            None => status!("  输出目录: 与输入文件相同"),
+        }
+        status!("  并发数:   {}", args.jobs.max(1));
+    } else {
+        status!("  输入:     {}", describe(&jobs[0].input, "标准输入"));
+        status!("  输出:     {}", describe(&jobs[0].output, "标准输出"));
+    }
+    status!("  格式:     {}", args.format.to_uppercase());
+    status!("  主题:     {}", args.theme);
+    status!("  字体大小: {}", or_theme(&font_size));
+    status!("  纸张:     {}", args.paper);
+    status!(
+        "  页边距:   上 {:.1}mm 右 {:.1}mm 下 {:.1}mm 左 {:.1}mm",
+        margins.top * 25.4,
+        margins.right * 25.4,
+        margins.bottom * 25.4,
+        margins.left * 25.4
+    );
+    status!("  中文字体: {}", or_theme(&args.chinese_font));
+    status!("  文字厚度: {}", or_theme(&args.font_weight));
+    status!("  行间距:   {}", or_theme(&args.line_spacing));
+    status!("  段落间距: {}", or_theme(&paragraph_spacing));
+    status!("  公式间距: {}", or_theme(&math_spacing));
+    status!("  代码主题: {}", args.code_theme);
     if args.landscape {
-        println!("  页面方向: 横向");
+        status!("  页面方向: 横向");
+    }
+    if args.embed_images {
+        status!("  内嵌图片: 是 (上限 {})", args.embed_max_size);
+    }
+    if let Some(layout) = &cover_layout {
+        status!("  封面:     {}", layout);
+    }
+    for path in &args.css {
+        status!("  CSS:      {}", path.display());
+    }
+    if let Some(template) = &args.template {
+        status!("  HTML模板: {}", template.display());
+    }
+    if let Some(header) = &args.header {
+        status!("  页眉:     {}", header);
+    }
+    if let Some(footer) = &args.footer {
+        status!("  页脚:     {}", footer);
+    }
+    if math == MathRender::Server {
+        status!("  公式渲染: 服务端 (静态 HTML)");
+    }
+    if equation_numbers != EquationNumbering::Labeled {
+        status!("  公式编号: {}", args.equation_numbers);
     }
-    println!();
+    if !args.bibliography.is_empty() {
+        let files: Vec<String> = args.bibliography.iter().map(|p| p.display().to_string()).collect();
+        status!("  参考文献: {} ({})", files.join(", "), args.citation_style);
+    }
+    status!();
 
     let style_opts = StyleOptions {
+        theme:              args.theme.clone(),
         font_size,
         chinese_font:       args.chinese_font.clone(),
         font_weight:        args.font_weight.clone(),
         line_spacing:       args.line_spacing.clone(),
         paragraph_spacing,
         math_spacing,
+        code_theme:         args.code_theme.clone(),
+        css_files:          args.css.clone(),
+        html_template:      args.template.clone(),
+    };
+    let pdf_opts = PdfOptions {
+        paper_width,
+        paper_height,
+        margins,
+        landscape: args.landscape,
+        header_template,
+        footer_template,
+        outline: !args.no_outline,
+        subject: args.subject.clone(),
+        keywords: args
+            .keywords
+            .iter()
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty())
+            .collect(),
+    };
+    let render_opts = RenderOptions {
+        math,
+        equation_numbers,
+        bibliography: args.bibliography.clone(),
+        citation_style,
+        toc: args.toc,
+        list_of_figures: args.list_of_figures,
+        list_of_tables: args.list_of_tables,
+        lang: args.lang.clone(),
+        line_numbers: args.line_numbers,
+        embed_images: args.embed_images,
+        embed_max_size,
+        cover: CoverOptions {
+            layout: cover_layout,
+            title: args.title.clone(),
+            subtitle: args.subtitle.clone(),
+            authors: args.authors.clone(),
+            organization: args.organization.clone(),
+            date: args.date.clone(),
+            logo: args.logo.clone(),
+            version: args.doc_version.clone(),
+        },
+        ..Default::default()
     };
-
-    //  Locate assets directory 
-    let assets_dir = resolve_assets_dir();
 
     let start = std::time::Instant::now();
 
+    //  Load KaTeX assets once for every document 
+    status!("加载 KaTeX 本地资源 (CSS, JS, 字体)...");
+    let mut builder = Converter::builder()
+        .style_options(style_opts)
+        .pdf_options(pdf_opts)
+        .render_options(render_opts)
+        .progress(!(is_batch || args.watch) || args.verbose);
+    if let Some(chrome) = &args.chrome {
+        builder = builder.chrome_path(chrome);
+    }
+    let converter = builder.build();
+
+    if args.watch {
+        // Header / footer templates read from files are re-read on change.
+        let templates = [&args.header, &args.footer]
+            .map(|value| value.as_deref().map(PathBuf::from).filter(|p| p.is_file()));
+        tokio::task::spawn_blocking(move || watch::run(converter, jobs, format, templates)).await??;
+        return Ok(());
+    }
+
+    if is_batch {
+        let summary = batch::run(converter, jobs, format, args.jobs).await;
+        print_summary(&summary, start.elapsed().as_secs_f32());
+        if !summary.failed.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let job = &jobs[0];
+    convert_single(converter, &job.input, &job.output, format).await?;
+    status!("\n转换完成! (耗时: {:.1}秒)", start.elapsed().as_secs_f32());
+    if !to_stdout {
+        status!("文件已生成: {}", job.output.display());
+    }
+
+    Ok(())
+}
+
+/// Convert one file, reporting each phase.
+async fn convert_single(
+    converter: Converter,
+    input: &Path,
+    output_path: &Path,
+    format: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
     //  Phase 1: read markdown 
-    println!("读取 Markdown 文件...");
-    let markdown = fs::read_to_string(&args.input)?;
-
-    //  Phase 2: load KaTeX assets 
-    println!("加载 KaTeX 本地资源 (CSS, JS, 字体)...");
-    let katex_css            = get_local_katex_css_with_inline_fonts(&assets_dir);
-    let katex_js             = get_local_katex_js(&assets_dir);
-    let katex_auto_render_js = get_local_katex_auto_render_js(&assets_dir);
-
-    //  Phase 3: render markdown + math  HTML fragment 
-    println!("渲染 HTML 内容...");
-    let html_body = render(&markdown);
-
-    //  Phase 4: wrap in full HTML document 
-    let title = args
-        .input
+    status!("读取 Markdown 文件...");
+    let from_stdin = is_std_stream(input);
+    let markdown = if from_stdin {
+        let mut markdown = String::new();
+        std::io::stdin().read_to_string(&mut markdown)?;
+        markdown
+    } else {
+        fs::read_to_string(input)?
+    };
+
+    //  Phase 2: render markdown + math, wrap in full HTML document 
+    status!("渲染 HTML 内容...");
+    // From stdin the title comes from the front matter or --title.
+    let title = input
         .file_stem()
         .and_then(|s| s.to_str())
+        .filter(|_| !from_stdin)
         .unwrap_or("Markdown to PDF")
         .to_string();
-    let full_html = generate_html_document(
-        &html_body,
-        &title,
-        &katex_css,
-        &katex_js,
-        &katex_auto_render_js,
-        &style_opts,
-    );
+    let base_dir = if from_stdin { None } else { input.parent() };
+    let doc = converter.render_document_in(&markdown, &title, base_dir);
 
-    //  Phase 5: output 
-    match args.format.as_str() {
-        "html" => {
-            println!("保存 HTML 文件...");
-            fs::write(&output_path, &full_html)?;
-            println!("\n转换完成! (耗时: {:.1}秒)", start.elapsed().as_secs_f32());
-            println!("文件已生成: {}", output_path.display());
-        }
-        "pdf" => {
-            let pdf_opts = PdfOptions {
-                margin_inches: margin_to_inches(&margin),
-                landscape: args.landscape,
-            };
-            let output_path_display = output_path.display().to_string();
+    //  Phase 3: output 
+    let to_stdout = is_std_stream(output_path);
+    let work_dir = output_path.parent().filter(|_| !to_stdout).map(|d| d.to_path_buf());
+    if let Some(dir) = &work_dir {
+        fs::create_dir_all(dir)?;
+    }
+    let data = match format {
+        OutputFormat::Html => {
+            status!("保存 HTML 文件...");
+            doc.html.into_bytes()
+        }
+        OutputFormat::Pdf => {
+            let work_dir = work_dir.clone();
             tokio::task::spawn_blocking(move || {
-                generate_pdf(&full_html, &output_path, &pdf_opts, args.chrome.as_deref())
+                converter.document_to_pdf(&doc, work_dir.as_deref())
             })
-            .await??;
-
-            println!("\n转换完成! (耗时: {:.1}秒)", start.elapsed().as_secs_f32());
-            println!("文件已生成: {}", output_path_display);
-        }
-        other => {
-            eprintln!("不支持的格式: {}", other);
-            std::process::exit(1);
+            .await??
         }
+    };
+    if to_stdout {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&data)?;
+        stdout.flush()?;
+    } else {
+        fs::write(output_path, data)?;
     }
 
     Ok(())
 }
+
+fn print_summary(summary: &batch::Summary, elapsed_secs: f32) {
+    status!(
+        "\n批量转换完成: 成功 {} 个, 失败 {} 个 (耗时: {:.1}秒)",
+        summary.succeeded.len(),
+        summary.failed.len(),
+        elapsed_secs
+    );
+    if !summary.succeeded.is_empty() {
+        status!("成功:");
+        for job in &summary.succeeded {
+            status!("  {} -> {}", job.input.display(), job.output.display());
+        }
+    }
+    if !summary.failed.is_empty() {
+        eprintln!("失败:");
+        for (job, err) in &summary.failed {
+            eprintln!("  {}: {}", job.input.display(), err);
+        }
+    }
+}
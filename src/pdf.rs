@@ -0,0 +1,454 @@
+//! pdf.rs — Post-processing of the PDFs Chrome prints: finding the page each
+//!          named destination landed on, stitching separately printed page
+//!          ranges back into one document, adding the bookmark outline and
+//!          writing the document metadata.
+
+use crate::converter::AppError;
+use lopdf::{text_string, Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Page attributes a page may inherit from its `/Pages` ancestors.
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+fn pdf_err(context: &str, e: lopdf::Error) -> AppError {
+    AppError::Pdf(format!("{}: {}", context, e))
+}
+
+fn load(pdf: &[u8]) -> Result<Document, AppError> {
+    Document::load_mem(pdf).map_err(|e| pdf_err("cannot parse PDF", e))
+}
+
+fn save(doc: &mut Document) -> Result<Vec<u8>, AppError> {
+    let mut out = Vec::new();
+    doc.save_to(&mut out)?;
+    Ok(out)
+}
+
+/// Number of pages in `pdf`.
+pub fn page_count(pdf: &[u8]) -> Result<u32, AppError> {
+    Ok(load(pdf)?.get_pages().len() as u32)
+}
+
+// ─────────────────────────────────────────────
+//  Named destinations
+// ─────────────────────────────────────────────
+
+/// All named destinations: the catalog's `/Dests` dictionary plus the
+/// `/Names` → `/Dests` name tree.
+fn named_destinations(doc: &Document) -> BTreeMap<Vec<u8>, Object> {
+    let mut dests = BTreeMap::new();
+    let Ok(catalog) = doc.catalog() else {
+        return dests;
+    };
+    if let Ok(Object::Dictionary(dict)) = catalog.get_deref(b"Dests", doc) {
+        for (name, dest) in dict.iter() {
+            dests.insert(name.clone(), dest.clone());
+        }
+    }
+    if let Ok(Object::Dictionary(names)) = catalog.get_deref(b"Names", doc) {
+        if let Ok(Object::Dictionary(tree)) = names.get_deref(b"Dests", doc) {
+            collect_name_tree(doc, tree, &mut dests);
+        }
+    }
+    dests
+}
+
+fn collect_name_tree(doc: &Document, node: &Dictionary, out: &mut BTreeMap<Vec<u8>, Object>) {
+    if let Ok(Object::Array(pairs)) = node.get_deref(b"Names", doc) {
+        for pair in pairs.chunks(2) {
+            if let [Object::String(name, _), dest] = pair {
+                out.insert(name.clone(), dest.clone());
+            }
+        }
+    }
+    if let Ok(Object::Array(kids)) = node.get_deref(b"Kids", doc) {
+        for kid in kids {
+            if let Ok((_, Object::Dictionary(kid))) = doc.dereference(kid) {
+                collect_name_tree(doc, kid, out);
+            }
+        }
+    }
+}
+
+/// The page object a destination (`[page /XYZ ...]` or `<< /D [...] >>`) points to.
+fn destination_page(doc: &Document, dest: &Object) -> Option<ObjectId> {
+    match doc.dereference(dest).ok()?.1 {
+        Object::Array(items) => items.first()?.as_reference().ok(),
+        Object::Dictionary(dict) => destination_page(doc, dict.get(b"D").ok()?),
+        _ => None,
+    }
+}
+
+/// 1-based page number of every named destination in `pdf`.
+/// Chrome creates one for each element that an internal `#id` link points to.
+pub fn destination_pages(pdf: &[u8]) -> Result<HashMap<String, u32>, AppError> {
+    let doc = load(pdf)?;
+    let page_numbers: HashMap<ObjectId, u32> =
+        doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect();
+    Ok(named_destinations(&doc)
+        .into_iter()
+        .filter_map(|(name, dest)| {
+            let page = page_numbers.get(&destination_page(&doc, &dest)?)?;
+            Some((String::from_utf8_lossy(&name).into_owned(), *page))
+        })
+        .collect())
+}
+
+// ─────────────────────────────────────────────
+//  Merging
+// ─────────────────────────────────────────────
+
+fn root_ids(doc: &Document) -> Result<(ObjectId, ObjectId), AppError> {
+    let catalog_id = doc
+        .trailer
+        .get(b"Root")
+        .and_then(Object::as_reference)
+        .map_err(|e| pdf_err("missing catalog", e))?;
+    let pages_id = doc
+        .catalog()
+        .and_then(|c| c.get(b"Pages"))
+        .and_then(Object::as_reference)
+        .map_err(|e| pdf_err("missing page tree", e))?;
+    Ok((catalog_id, pages_id))
+}
+
+/// Hang every page directly below `root`, copying down attributes it
+/// inherited from intermediate `/Pages` nodes.  Returns the pages in order.
+fn flatten_page_tree(doc: &mut Document, root: ObjectId) -> Vec<ObjectId> {
+    let pages: Vec<ObjectId> = doc.get_pages().into_values().collect();
+    for &page_id in &pages {
+        let mut inherited = Vec::new();
+        if let Ok(page) = doc.get_dictionary(page_id) {
+            for key in INHERITABLE.iter().filter(|k| !page.has(k)) {
+                let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+                while let Some(node) = parent.and_then(|id| doc.get_dictionary(id).ok()) {
+                    if let Ok(value) = node.get(key) {
+                        inherited.push((key.to_vec(), value.clone()));
+                        break;
+                    }
+                    parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+                }
+            }
+        }
+        if let Ok(page) = doc.get_dictionary_mut(page_id) {
+            for (key, value) in inherited {
+                page.set(key, value);
+            }
+            page.set("Parent", root);
+        }
+    }
+    pages
+}
+
+/// Concatenate the pages of several PDFs printed from the same document
+/// (e.g. page ranges printed with different headers).  Named destinations of
+/// all parts are kept so internal links keep working; outlines and the
+/// structure tree only describe one part and are dropped.
+pub fn merge(parts: &[Vec<u8>]) -> Result<Vec<u8>, AppError> {
+    let (first, rest) = parts
+        .split_first()
+        .ok_or_else(|| AppError::Pdf("nothing to merge".to_string()))?;
+    let mut merged = load(first)?;
+    let (catalog_id, pages_id) = root_ids(&merged)?;
+    let mut kids = flatten_page_tree(&mut merged, pages_id);
+    let mut dests = named_destinations(&merged);
+
+    for part in rest {
+        let mut doc = load(part)?;
+        doc.renumber_objects_with(merged.max_id + 1);
+        let (part_catalog, part_pages) = root_ids(&doc)?;
+        let pages = flatten_page_tree(&mut doc, pages_id);
+        for (name, dest) in named_destinations(&doc) {
+            dests.entry(name).or_insert(dest);
+        }
+        doc.objects.remove(&part_catalog);
+        doc.objects.remove(&part_pages);
+        merged.max_id = merged.max_id.max(doc.max_id);
+        merged.objects.extend(doc.objects);
+        kids.extend(pages);
+    }
+
+    let count = kids.len() as u32;
+    let pages = merged
+        .get_dictionary_mut(pages_id)
+        .map_err(|e| pdf_err("missing page tree", e))?;
+    pages.set("Kids", kids.into_iter().map(Object::Reference).collect::<Vec<_>>());
+    pages.set("Count", count);
+
+    let catalog = merged
+        .get_dictionary_mut(catalog_id)
+        .map_err(|e| pdf_err("missing catalog", e))?;
+    let mut dest_dict = Dictionary::new();
+    for (name, dest) in dests {
+        dest_dict.set(name, dest);
+    }
+    catalog.set("Dests", dest_dict);
+    catalog.remove(b"Names");
+    for key in [&b"Outlines"[..], b"StructTreeRoot", b"MarkInfo"] {
+        catalog.remove(key);
+    }
+
+    merged.prune_objects();
+    save(&mut merged)
+}
+
+// ─────────────────────────────────────────────
+//  Outline
+// ─────────────────────────────────────────────
+
+/// One bookmark: a heading and the named destination Chrome placed on it.
+#[derive(Debug, Clone)]
+pub struct OutlineItem {
+    /// Heading level, 1–6.  Skipped levels nest under the nearest shallower item.
+    pub level: u8,
+    pub title: String,
+    pub destination: String,
+}
+
+struct OutlineNode<'a> {
+    item: &'a OutlineItem,
+    /// Explicit destination (`[page /XYZ left top zoom]`) of the item.
+    dest: Object,
+    children: Vec<usize>,
+}
+
+/// Write the `siblings` nodes and their subtrees below `parent`; returns how
+/// many items (at any depth) were written.
+fn write_outline_level(
+    doc: &mut Document,
+    nodes: &[OutlineNode],
+    ids: &[ObjectId],
+    siblings: &[usize],
+    parent: ObjectId,
+) -> usize {
+    let mut total = 0;
+    for (i, &n) in siblings.iter().enumerate() {
+        let node = &nodes[n];
+        let descendants = write_outline_level(doc, nodes, ids, &node.children, ids[n]);
+        let mut dict = Dictionary::new();
+        dict.set("Title", text_string(&node.item.title));
+        dict.set("Parent", parent);
+        dict.set("Dest", node.dest.clone());
+        if i > 0 {
+            dict.set("Prev", ids[siblings[i - 1]]);
+        }
+        if let Some(&next) = siblings.get(i + 1) {
+            dict.set("Next", ids[next]);
+        }
+        if let (Some(&first), Some(&last)) = (node.children.first(), node.children.last()) {
+            dict.set("First", ids[first]);
+            dict.set("Last", ids[last]);
+            dict.set("Count", descendants as i64);
+        }
+        doc.objects.insert(ids[n], Object::Dictionary(dict));
+        total += 1 + descendants;
+    }
+    total
+}
+
+/// Replace the outline of `pdf` with one bookmark per item, nested by level
+/// and fully expanded.  Items whose destination is not in the PDF are left
+/// out.  Returns `pdf` unchanged when no item remains.
+pub fn add_outline(pdf: &[u8], items: &[OutlineItem]) -> Result<Vec<u8>, AppError> {
+    let mut doc = load(pdf)?;
+    let known = named_destinations(&doc);
+    let explicit = |name: &str| -> Option<Object> {
+        match doc.dereference(known.get(name.as_bytes())?).ok()?.1 {
+            Object::Dictionary(dict) => doc.dereference(dict.get(b"D").ok()?).ok().map(|d| d.1.clone()),
+            dest => Some(dest.clone()),
+        }
+    };
+
+    let mut nodes: Vec<OutlineNode> = Vec::new();
+    let mut roots: Vec<usize> = Vec::new();
+    let mut open: Vec<usize> = Vec::new(); // path from a root to the last node
+    for item in items {
+        let Some(dest) = explicit(&item.destination) else {
+            continue;
+        };
+        while open.last().is_some_and(|&n| nodes[n].item.level >= item.level) {
+            open.pop();
+        }
+        let index = nodes.len();
+        nodes.push(OutlineNode { item, dest, children: Vec::new() });
+        match open.last() {
+            Some(&parent) => nodes[parent].children.push(index),
+            None => roots.push(index),
+        }
+        open.push(index);
+    }
+    if nodes.is_empty() {
+        return Ok(pdf.to_vec());
+    }
+
+    let outlines_id = doc.new_object_id();
+    let ids: Vec<ObjectId> = nodes.iter().map(|_| doc.new_object_id()).collect();
+    let count = write_outline_level(&mut doc, &nodes, &ids, &roots, outlines_id);
+    let mut outlines = Dictionary::new();
+    outlines.set("Type", "Outlines");
+    outlines.set("First", ids[roots[0]]);
+    outlines.set("Last", ids[roots[roots.len() - 1]]);
+    outlines.set("Count", count as i64);
+    doc.objects.insert(outlines_id, Object::Dictionary(outlines));
+
+    let catalog = doc
+        .catalog_mut()
+        .map_err(|e| pdf_err("missing catalog", e))?;
+    catalog.set("Outlines", outlines_id);
+    catalog.set("PageMode", "UseOutlines");
+
+    doc.prune_objects();
+    save(&mut doc)
+}
+
+// ─────────────────────────────────────────────
+//  Metadata
+// ─────────────────────────────────────────────
+
+/// Document properties written to the Info dictionary and XMP metadata.
+/// Empty fields keep whatever Chrome wrote (it takes the title from `<title>`).
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+/// Creating application, e.g. `md2pdf-rs 0.1.0`.
+fn creator() -> String {
+    format!("md2pdf-rs {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Civil date `(year, month, day)` of a day count since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Current UTC time as `(PDF date, XMP date)`:
+/// `D:20240131120000Z` and `2024-01-31T12:00:00Z`.
+fn now_dates() -> (String, String) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let (y, mo, d) = civil_from_days(secs.div_euclid(86_400));
+    let t = secs.rem_euclid(86_400);
+    let (h, mi, s) = (t / 3600, t / 60 % 60, t % 60);
+    (
+        format!("D:{:04}{:02}{:02}{:02}{:02}{:02}Z", y, mo, d, h, mi, s),
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, h, mi, s),
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// XMP packet mirroring the Info dictionary (Dublin Core, PDF and XMP basic
+/// schemas), for readers that prefer it.
+fn xmp_packet(meta: &Metadata, creator: &str, date: &str) -> String {
+    let mut props = String::new();
+    let alt = |value: &str| {
+        format!("<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>", xml_escape(value))
+    };
+    if let Some(title) = &meta.title {
+        props.push_str(&format!("      <dc:title>{}</dc:title>\n", alt(title)));
+    }
+    if !meta.authors.is_empty() {
+        let items: String = meta
+            .authors
+            .iter()
+            .map(|a| format!("<rdf:li>{}</rdf:li>", xml_escape(a)))
+            .collect();
+        props.push_str(&format!("      <dc:creator><rdf:Seq>{}</rdf:Seq></dc:creator>\n", items));
+    }
+    if let Some(subject) = &meta.subject {
+        props.push_str(&format!("      <dc:description>{}</dc:description>\n", alt(subject)));
+    }
+    if !meta.keywords.is_empty() {
+        let items: String = meta
+            .keywords
+            .iter()
+            .map(|k| format!("<rdf:li>{}</rdf:li>", xml_escape(k)))
+            .collect();
+        props.push_str(&format!("      <dc:subject><rdf:Bag>{}</rdf:Bag></dc:subject>\n", items));
+        props.push_str(&format!(
+            "      <pdf:Keywords>{}</pdf:Keywords>\n",
+            xml_escape(&meta.keywords.join(", "))
+        ));
+    }
+    format!(
+        r#"<?xpacket begin="{}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about=""
+        xmlns:dc="http://purl.org/dc/elements/1.1/"
+        xmlns:pdf="http://ns.adobe.com/pdf/1.3/"
+        xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+      <dc:format>application/pdf</dc:format>
+{}      <xmp:CreatorTool>{}</xmp:CreatorTool>
+      <xmp:CreateDate>{date}</xmp:CreateDate>
+      <xmp:ModifyDate>{date}</xmp:ModifyDate>
+      <xmp:MetadataDate>{date}</xmp:MetadataDate>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        '\u{feff}',
+        props,
+        xml_escape(creator),
+    )
+}
+
+/// Write `meta`, the creator and the creation date into the Info dictionary
+/// (as text strings, UTF-16 when not plain ASCII) and an XMP metadata stream.
+/// Chrome's own `/Producer` is kept.
+pub fn set_metadata(pdf: &[u8], meta: &Metadata) -> Result<Vec<u8>, AppError> {
+    let mut doc = load(pdf)?;
+    let creator = creator();
+    let (pdf_date, xmp_date) = now_dates();
+
+    let mut info = match doc.trailer.get(b"Info").ok().and_then(|i| doc.dereference(i).ok()) {
+        Some((_, Object::Dictionary(dict))) => dict.clone(),
+        _ => Dictionary::new(),
+    };
+    let authors = meta.authors.join(", ");
+    let keywords = meta.keywords.join(", ");
+    let fields = [
+        ("Title", meta.title.as_deref().unwrap_or_default()),
+        ("Author", authors.as_str()),
+        ("Subject", meta.subject.as_deref().unwrap_or_default()),
+        ("Keywords", keywords.as_str()),
+    ];
+    for (key, value) in fields.into_iter().filter(|(_, v)| !v.is_empty()) {
+        info.set(key, text_string(value));
+    }
+    info.set("Creator", text_string(&creator));
+    info.set("CreationDate", Object::string_literal(pdf_date.as_str()));
+    info.set("ModDate", Object::string_literal(pdf_date));
+    let info_id = doc.add_object(info);
+    doc.trailer.set("Info", info_id);
+
+    let mut xmp = Stream::new(Dictionary::new(), xmp_packet(meta, &creator, &xmp_date).into_bytes());
+    xmp.dict.set("Type", "Metadata");
+    xmp.dict.set("Subtype", "XML");
+    // Readers scan for the packet; it must stay uncompressed.
+    xmp.allows_compression = false;
+    let xmp_id = doc.add_object(xmp);
+    doc.catalog_mut()
+        .map_err(|e| pdf_err("missing catalog", e))?
+        .set("Metadata", xmp_id);
+
+    doc.prune_objects();
+    save(&mut doc)
+}
@@ -1,8 +1,131 @@
-/// renderer.rs — Markdown + LaTeX math rendering pipeline.
-///               Mirrors renderer.js.
+//! renderer.rs — Markdown + LaTeX math rendering pipeline.
+//!               Mirrors renderer.js.
 
-use pulldown_cmark::{html, Options, Parser};
+use crate::config::RenderOptions;
+use crate::admonition::{fence_containers, render_alerts};
+use crate::bibliography::{cite, load_bibliography};
+use crate::crossref::{caption_separator, number_floats, resolve_equations, FloatKind, Floats};
+use crate::highlight::highlight_code;
+use crate::katex_engine::KatexEngine;
+use crate::theme::{data_url, mime_type};
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag};
 use regex::Regex;
+use serde_yaml::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+// ─────────────────────────────────────────────
+//  Front matter
+// ─────────────────────────────────────────────
+
+/// Metadata and option overrides from a leading `---` YAML block.
+#[derive(Debug, Clone, Default)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub date: Option<String>,
+    pub lang: Option<String>,
+    /// `subject` or `description`: the PDF's subject.
+    pub subject: Option<String>,
+    pub keywords: Vec<String>,
+    /// Every other scalar key (kebab-cased), e.g. `font-size` or `margin`.
+    pub options: BTreeMap<String, String>,
+}
+
+impl FrontMatter {
+    /// Option value by kebab-case key.
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(String::as_str)
+    }
+}
+
+fn yaml_scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// A scalar, a sequence of scalars, or a comma-separated string.
+fn yaml_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::Sequence(items) => items.iter().filter_map(yaml_scalar).collect(),
+        Value::String(s) => s
+            .split([',', '，'])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect(),
+        other => yaml_scalar(other).into_iter().collect(),
+    }
+}
+
+/// `None` when the block is not a YAML mapping (e.g. a `---` rule around text).
+fn parse_front_matter(yaml: &str) -> Result<Option<FrontMatter>, serde_yaml::Error> {
+    let mut fm = FrontMatter::default();
+    let map = match serde_yaml::from_str::<Value>(yaml)? {
+        Value::Mapping(map) => map,
+        Value::Null => return Ok(Some(fm)),
+        _ => return Ok(None),
+    };
+
+    for (key, value) in &map {
+        let Some(key) = key.as_str() else { continue };
+        let key = key.to_lowercase().replace('_', "-");
+        match key.as_str() {
+            "title" => fm.title = yaml_scalar(value),
+            "author" | "authors" => fm.authors = yaml_list(value),
+            "date" => fm.date = yaml_scalar(value),
+            "lang" | "language" => fm.lang = yaml_scalar(value),
+            "subject" | "description" => fm.subject = yaml_scalar(value),
+            "keywords" | "tags" => fm.keywords = yaml_list(value),
+            _ => {
+                if let Some(v) = yaml_scalar(value) {
+                    fm.options.insert(key, v);
+                }
+            }
+        }
+    }
+    Ok(Some(fm))
+}
+
+/// Split a leading YAML front matter block (`---` … `---` or `...`) off the
+/// markdown.  Returns the parsed metadata (if any) and the remaining body.
+/// Invalid YAML is reported and the document is left untouched.
+pub fn split_front_matter(content: &str) -> (Option<FrontMatter>, &str) {
+    let text = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let Some(rest) = text
+        .strip_prefix("---\r\n")
+        .or_else(|| text.strip_prefix("---\n"))
+    else {
+        return (None, content);
+    };
+
+    let mut offset = 0usize;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            let yaml = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            return match parse_front_matter(yaml) {
+                Ok(Some(fm)) => (Some(fm), body),
+                Ok(None) => (None, content),
+                Err(e) => {
+                    eprintln!("Warning: invalid YAML front matter ignored: {}", e);
+                    (None, content)
+                }
+            };
+        }
+        offset += line.len();
+    }
+
+    (None, content)
+}
 
 // ─────────────────────────────────────────────
 //  Math expression extraction
@@ -21,19 +144,53 @@ pub struct MathExpr {
     pub placeholder: String,
 }
 
+/// Byte ranges of fenced / indented code blocks and inline code spans in `content`.
+/// Math delimiters inside these ranges are left untouched.
+pub fn find_code_regions(content: &str) -> Vec<Range<usize>> {
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    for (event, range) in Parser::new_ext(content, markdown_options()).into_offset_iter() {
+        let is_code = matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Code(_));
+        // Code spans never nest inside code blocks, but stay defensive.
+        if is_code && regions.last().is_none_or(|last| range.start >= last.end) {
+            regions.push(range);
+        }
+    }
+    regions
+}
+
 /// Extract all math expressions and replace them with HTML-comment placeholders.
-/// Processing order matches renderer.js:
+/// Code blocks and inline code spans (see [`find_code_regions`]) are copied
+/// through verbatim; every other stretch of text is processed in the order
+/// used by renderer.js:
 ///   1. Block: `$$...$$` and `\[...\]`  (must come before inline)
 ///   2. Inline: `$...$` and `\(...\)`
 ///
 /// An inline expression containing a newline is promoted to block.
 pub fn process_math_expressions(content: &str) -> (String, Vec<MathExpr>) {
     let mut exprs: Vec<MathExpr> = Vec::new();
+    let mut text = String::with_capacity(content.len());
+    let mut last = 0usize;
+
+    for region in find_code_regions(content) {
+        text.push_str(&extract_math(&content[last..region.start], &mut exprs));
+        text.push_str(&content[region.clone()]);
+        last = region.end;
+    }
+    text.push_str(&extract_math(&content[last..], &mut exprs));
+
+    (text, exprs)
+}
+
+/// Run the math extraction passes over a stretch of markdown that contains no code.
+fn extract_math(content: &str, exprs: &mut Vec<MathExpr>) -> String {
+    static DISPLAY_DOLLARS: OnceLock<Regex> = OnceLock::new();
+    static DISPLAY_BRACKETS: OnceLock<Regex> = OnceLock::new();
+    static INLINE_PARENS: OnceLock<Regex> = OnceLock::new();
     let mut text = content.to_string();
 
     // ── Block: $$...$$ ────────────────────────────────────────────────────────
     {
-        let re = Regex::new(r"(?s)\$\$([\s\S]*?)\$\$").unwrap();
+        let re = DISPLAY_DOLLARS.get_or_init(|| Regex::new(r"(?s)\$\$([\s\S]*?)\$\$").unwrap());
         let mut result = String::new();
         let mut last = 0usize;
         for cap in re.captures_iter(&text.clone()) {
@@ -56,7 +213,7 @@ pub fn process_math_expressions(content: &str) -> (String, Vec<MathExpr>) {
 
     // ── Block: \[...\] ───────────────────────────────────────────────────────
     {
-        let re = Regex::new(r"(?s)\\\[([\s\S]*?)\\\]").unwrap();
+        let re = DISPLAY_BRACKETS.get_or_init(|| Regex::new(r"(?s)\\\[([\s\S]*?)\\\]").unwrap());
         let mut result = String::new();
         let mut last = 0usize;
         for cap in re.captures_iter(&text.clone()) {
@@ -137,7 +294,7 @@ pub fn process_math_expressions(content: &str) -> (String, Vec<MathExpr>) {
 
     // ── Inline: \(...\) ──────────────────────────────────────────────────────
     {
-        let re = Regex::new(r"(?s)\\\(([\s\S]*?)\\\)").unwrap();
+        let re = INLINE_PARENS.get_or_init(|| Regex::new(r"(?s)\\\(([\s\S]*?)\\\)").unwrap());
         let mut result = String::new();
         let mut last = 0usize;
         for cap in re.captures_iter(&text.clone()) {
@@ -163,7 +320,7 @@ pub fn process_math_expressions(content: &str) -> (String, Vec<MathExpr>) {
         text = result;
     }
 
-    (text, exprs)
+    text
 }
 
 // ─────────────────────────────────────────────
@@ -183,12 +340,29 @@ pub fn generate_math_html(tex: &str, is_block: bool) -> String {
     }
 }
 
+/// Wrap KaTeX output that was already typeset on the server.
+pub fn generate_static_math_html(katex_html: &str, is_block: bool) -> String {
+    if is_block {
+        format!(r#"<div class="math-block">{}</div>"#, katex_html)
+    } else {
+        format!(r#"<span class="math-inline">{}</span>"#, katex_html)
+    }
+}
+
 // ─────────────────────────────────────────────
 //  Markdown rendering
 // ─────────────────────────────────────────────
 
+/// Whether generated text (the table of contents title, caption labels, ...)
+/// should be Chinese: `lang` is `zh*`, or unset and `text` contains CJK.
+pub(crate) fn is_chinese(lang: Option<&str>, text: &str) -> bool {
+    match lang {
+        Some(lang) => lang.to_ascii_lowercase().starts_with("zh"),
+        None => text.chars().any(|c| ('\u{4e00}'..='\u{9fff}').contains(&c)),
+    }
+}
+
 /// Escape HTML special characters.  Mirrors `escapeHtml` in utils.js.
-#[allow(dead_code)]
 pub fn escape_html(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -197,38 +371,569 @@ pub fn escape_html(s: &str) -> String {
         .replace('\'', "&#39;")
 }
 
-/// Render Markdown source (math already replaced by placeholders) to an HTML fragment.
-/// Uses pulldown-cmark with strikethrough, tables, footnotes and task-lists.
-pub fn render_markdown(content: &str) -> String {
+/// pulldown-cmark extensions: strikethrough, tables, footnotes, task-lists and
+/// `{#id .class}` heading attributes.
+pub(crate) fn markdown_options() -> Options {
     let mut opts = Options::empty();
     opts.insert(Options::ENABLE_STRIKETHROUGH);
     opts.insert(Options::ENABLE_TABLES);
     opts.insert(Options::ENABLE_FOOTNOTES);
     opts.insert(Options::ENABLE_TASKLISTS);
+    opts.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    opts
+}
+
+/// Replace every code block with its syntax-highlighted HTML.
+fn highlight_code_blocks(events: Vec<Event>, line_numbers: bool) -> Vec<Event> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut block: Option<(String, String)> = None; // (language, code)
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    // Info strings may carry more than the language: "rust,ignore", "python {.x}".
+                    CodeBlockKind::Fenced(info) => info
+                        .split(|c: char| c.is_whitespace() || c == ',' || c == '{')
+                        .next()
+                        .unwrap_or("")
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                block = Some((lang, String::new()));
+            }
+            Event::Text(text) if block.is_some() => {
+                if let Some((_, code)) = block.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                let Some((lang, code)) = block.take() else { continue };
+                let pre_class = if line_numbers { "hl-code line-numbers" } else { "hl-code" };
+                let code_class = if lang.is_empty() {
+                    String::new()
+                } else {
+                    format!(r#" class="language-{}""#, escape_html(&lang))
+                };
+                out.push(Event::Html(
+                    format!(
+                        "<pre class=\"{}\"><code{}>{}</code></pre>\n",
+                        pre_class,
+                        code_class,
+                        highlight_code(&code, &lang, line_numbers)
+                    )
+                    .into(),
+                ));
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Render Markdown source (math already replaced by placeholders) to an HTML fragment.
+/// Every heading gets an `id` (see [`heading_ids`]) and code is highlighted.
+pub fn render_markdown(content: &str) -> String {
+    render_markdown_with_headings(content, &RenderOptions::default()).0
+}
 
-    let parser = Parser::new_ext(content, opts);
+/// Like [`render_markdown`], also returning the headings for the table of
+/// contents and the captioned figures and tables.  Alerts and `:::`
+/// containers become callout boxes (see [`crate::admonition`]).
+fn render_markdown_with_headings(content: &str, opts: &RenderOptions) -> (String, Vec<TocHeading>, Floats) {
+    let content = fence_containers(content);
+    let events: Vec<Event> = Parser::new_ext(&content, markdown_options()).collect();
+    let headings = collect_headings(&events);
+    let events = highlight_code_blocks(events, opts.line_numbers);
+    let heading_text: String = headings.iter().map(|h| h.text.as_str()).collect();
+    let (events, floats) = number_floats(events, opts.lang.as_deref(), &heading_text);
+    let events = render_alerts(events, opts.lang.as_deref(), &heading_text);
+
+    let mut next_heading = headings.iter();
+    let events = events.into_iter().map(|event| match event {
+        Event::Start(Tag::Heading(level, _, classes)) => {
+            let id = next_heading.next().map(|h| h.id.as_str());
+            Event::Start(Tag::Heading(level, id, classes))
+        }
+        Event::Start(Tag::Image(kind, url, title)) if opts.embed_images && is_local_url(&url) => {
+            let url = embedded_image(&url, opts).map(CowStr::from).unwrap_or(url);
+            Event::Start(Tag::Image(kind, url, title))
+        }
+        other => other,
+    });
     let mut html_out = String::new();
-    html::push_html(&mut html_out, parser);
+    html::push_html(&mut html_out, events);
 
-    html_out
+    let html_out = html_out
         .replace("<p></p>", "")
-        .replace("<p>\n</p>", "")
+        .replace("<p>\n</p>", "");
+    (html_out, headings, floats)
+}
+
+/// Whether `url` points at a local file: no scheme, not a data URI, not a
+/// fragment.
+fn is_local_url(url: &str) -> bool {
+    !url.is_empty() && !url.contains("://") && !url.starts_with("data:") && !url.starts_with('#')
+}
+
+/// URLs of all images referenced by the markdown that point at local files
+/// (no scheme, not a data URI), in document order.
+pub fn local_image_urls(content: &str) -> Vec<String> {
+    Parser::new_ext(content, markdown_options())
+        .filter_map(|event| match event {
+            Event::Start(Tag::Image(_, url, _)) => Some(url.to_string()),
+            _ => None,
+        })
+        .filter(|url| is_local_url(url))
+        .collect()
+}
+
+// ─────────────────────────────────────────────
+//  Image embedding
+// ─────────────────────────────────────────────
+
+/// Decode `%XX` escapes (e.g. `my%20image.png`); invalid escapes are kept.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                out.push(byte);
+                i += 3;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Data URI with the contents of the local image `url`, resolved against
+/// `opts.base_dir`.  Missing, unknown and oversized images are reported and
+/// give `None`, so the original URL is kept.
+fn embedded_image(url: &str, opts: &RenderOptions) -> Option<String> {
+    let file = percent_decode(url.split(['?', '#']).next().unwrap_or(url));
+    let path = match &opts.base_dir {
+        Some(dir) => dir.join(&file),
+        None => PathBuf::from(&file),
+    };
+    let Some(mime) = mime_type(&path) else {
+        eprintln!("Warning: not embedding {}: unknown image type", url);
+        return None;
+    };
+    let size = match fs::metadata(&path) {
+        Ok(meta) => meta.len(),
+        Err(e) => {
+            eprintln!("Warning: image not found: {} ({})", path.display(), e);
+            return None;
+        }
+    };
+    if size > opts.embed_max_size {
+        eprintln!(
+            "Warning: not embedding {}: {:.1} KB exceeds the {:.1} KB limit",
+            url,
+            size as f64 / 1024.0,
+            opts.embed_max_size as f64 / 1024.0
+        );
+        return None;
+    }
+    match data_url(&path, mime) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            eprintln!("Warning: cannot read image {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// ─────────────────────────────────────────────
+//  Table of contents
+// ─────────────────────────────────────────────
+
+/// Headings deeper than this are left out of the table of contents.
+const TOC_MAX_LEVEL: usize = 3;
+
+/// Stand in for `[TOC]`, `[LOF]` and `[LOT]` marker lines until the table of
+/// contents and the lists of figures and tables are generated.
+const TOC_PLACEHOLDER: &str = "<!--MD2PDF_TOC-->";
+const LOF_PLACEHOLDER: &str = "<!--MD2PDF_LOF-->";
+const LOT_PLACEHOLDER: &str = "<!--MD2PDF_LOT-->";
+
+/// A heading as listed in the table of contents.
+#[derive(Debug, Clone)]
+struct TocHeading {
+    level: usize,
+    id: String,
+    /// Inline HTML of the heading text, without links and footnote references.
+    html: String,
+    text: String,
+}
+
+/// GitHub's heading slug: lowercase, punctuation and symbols removed, spaces
+/// turned into hyphens ("Hello, World!" → "hello-world", "安装 Rust" → "安装-rust").
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Heading ids: the `{#custom-id}` when given, otherwise the GitHub slug of
+/// the text with `-1`, `-2`, ... appended to repeats.
+fn heading_ids(headings: &[(Option<String>, String)]) -> Vec<String> {
+    let mut used: HashSet<String> = headings.iter().filter_map(|(id, _)| id.clone()).collect();
+    let mut repeats: HashMap<String, usize> = HashMap::new();
+    headings
+        .iter()
+        .map(|(custom, text)| {
+            if let Some(id) = custom {
+                return id.clone();
+            }
+            let mut base = slugify(text);
+            if base.is_empty() {
+                base = "section".to_string();
+            }
+            let mut id = base.clone();
+            while used.contains(&id) {
+                let n = repeats.entry(base.clone()).or_insert(0);
+                *n += 1;
+                id = format!("{}-{}", base, n);
+            }
+            used.insert(id.clone());
+            id
+        })
+        .collect()
+}
+
+/// Headings of a parsed document, in order.
+fn collect_headings(events: &[Event]) -> Vec<TocHeading> {
+    let mut found = Vec::new(); // (level, custom id, text, inline html)
+    let mut current: Option<(HeadingLevel, Option<String>, Vec<Event>)> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading(level, id, _)) => {
+                current = Some((*level, id.map(str::to_string), Vec::new()))
+            }
+            Event::End(Tag::Heading(..)) => {
+                let Some((level, id, inner)) = current.take() else { continue };
+                let text: String = inner
+                    .iter()
+                    .filter_map(|e| match e {
+                        Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                let mut html_out = String::new();
+                html::push_html(&mut html_out, inner.into_iter());
+                found.push((level as usize, id, text, html_out));
+            }
+            Event::Start(Tag::Link(..)) | Event::End(Tag::Link(..)) | Event::FootnoteReference(_) => {}
+            e => {
+                if let Some((_, _, inner)) = current.as_mut() {
+                    inner.push(e.clone());
+                }
+            }
+        }
+    }
+
+    let ids = heading_ids(
+        &found.iter().map(|(_, id, text, _)| (id.clone(), text.clone())).collect::<Vec<_>>(),
+    );
+    found
+        .into_iter()
+        .zip(ids)
+        .map(|((level, _, text, html), id)| TocHeading { level, id, html, text })
+        .collect()
+}
+
+/// Replace `[name]` marker lines (e.g. `[TOC]`, any case) outside code with
+/// `placeholder`.  Returns the new source and whether a marker was found.
+fn replace_markers(content: &str, name: &str, placeholder: &str) -> (String, bool) {
+    static MARKER: OnceLock<Regex> = OnceLock::new();
+    let re = MARKER.get_or_init(|| Regex::new(r"(?m)^[ \t]*\[([A-Za-z]+)\][ \t]*$").unwrap());
+    let code = find_code_regions(content);
+    let mut found = false;
+    let replaced = re.replace_all(content, |cap: &regex::Captures| {
+        let start = cap.get(0).map_or(0, |m| m.start());
+        if !cap[1].eq_ignore_ascii_case(name) || code.iter().any(|r| r.contains(&start)) {
+            cap[0].to_string()
+        } else {
+            found = true;
+            placeholder.to_string()
+        }
+    });
+    (replaced.into_owned(), found)
+}
+
+/// Heading of the table of contents, in the document's language (guessed from
+/// the headings when `lang` is unset).
+fn toc_title(lang: Option<&str>, headings: &[TocHeading]) -> &'static str {
+    let text: String = headings.iter().map(|h| h.text.as_str()).collect();
+    if is_chinese(lang, &text) { "目录" } else { "Contents" }
+}
+
+/// Nested list of the headings down to [`TOC_MAX_LEVEL`].  Each entry links to
+/// its heading and has an empty `.toc-page` the PDF printer fills in.
+fn toc_html(headings: &[TocHeading], lang: Option<&str>) -> String {
+    let listed: Vec<&TocHeading> = headings.iter().filter(|h| h.level <= TOC_MAX_LEVEL).collect();
+    let top = listed.iter().map(|h| h.level).min().unwrap_or(1);
+
+    let mut out = format!(
+        "<nav class=\"toc\">\n<div class=\"toc-title\">{}</div>\n",
+        toc_title(lang, headings)
+    );
+    let mut depth = 0;
+    for h in listed {
+        let d = h.level - top + 1;
+        if d > depth {
+            // Skipped levels get an empty item to hang the nested list on.
+            while depth < d {
+                out.push_str("<ul>\n");
+                depth += 1;
+                if depth < d {
+                    out.push_str("<li>");
+                }
+            }
+        } else {
+            out.push_str("</li>\n");
+            while depth > d {
+                out.push_str("</ul>\n</li>\n");
+                depth -= 1;
+            }
+        }
+        out.push_str(&format!(
+            r##"<li><a href="#{id}"><span class="toc-text">{text}</span><span class="toc-leader"></span><span class="toc-page" data-target="{id}"></span></a>"##,
+            id = escape_html(&h.id),
+            text = h.html,
+        ));
+    }
+    if depth > 0 {
+        out.push_str("</li>\n");
+        while depth > 1 {
+            out.push_str("</ul>\n</li>\n");
+            depth -= 1;
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</nav>\n");
+    out
+}
+
+/// List of figures or tables: every captioned one, linking to it, with an
+/// empty `.toc-page` like the table of contents.  Empty without any.
+fn float_list_html(floats: &Floats, kind: FloatKind) -> String {
+    let entries: String = floats
+        .list
+        .iter()
+        .filter(|f| f.kind == kind)
+        .map(|f| {
+            format!(
+                r##"<li><a href="#{id}"><span class="toc-text">{label}{sep}{caption}</span><span class="toc-leader"></span><span class="toc-page" data-target="{id}"></span></a></li>
+"##,
+                id = escape_html(&f.id),
+                label = escape_html(&f.label),
+                sep = caption_separator(floats.chinese, &f.caption),
+                caption = f.caption.trim(),
+            )
+        })
+        .collect();
+    if entries.is_empty() {
+        return String::new();
+    }
+    let (class, title) = match (kind, floats.chinese) {
+        (FloatKind::Figure, false) => ("figures", "List of Figures"),
+        (FloatKind::Figure, true) => ("figures", "插图目录"),
+        (FloatKind::Table, false) => ("tables", "List of Tables"),
+        (FloatKind::Table, true) => ("tables", "表格目录"),
+    };
+    format!(
+        "<nav class=\"toc toc-{}\">\n<div class=\"toc-title\">{}</div>\n<ul>\n{}</ul>\n</nav>\n",
+        class, title, entries
+    )
 }
 
 // ─────────────────────────────────────────────
 //  Full render pipeline
 // ─────────────────────────────────────────────
 
+/// Format the citations against the entries of `opts.bibliography`; files
+/// that fail to load are reported and skipped.
+fn cite_references(content: &str, opts: &RenderOptions) -> String {
+    if opts.bibliography.is_empty() {
+        return content.to_string();
+    }
+    let mut refs = Vec::new();
+    for path in &opts.bibliography {
+        match load_bibliography(path) {
+            Ok(entries) => refs.extend(entries),
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+    cite(content, &refs, opts.citation_style, is_chinese(opts.lang.as_deref(), content))
+}
+
 /// Extract math → render markdown → restore math.
 /// Mirrors `MarkdownLatexRenderer.render()`.
-pub fn render(content: &str) -> String {
-    let (processed, math_exprs) = process_math_expressions(content);
-    let mut html = render_markdown(&processed);
+///
+/// With an `engine` (for [`crate::config::MathRender::Server`]) the
+/// expressions are typeset by KaTeX; without one, or where KaTeX fails on an
+/// expression, they get client-side markup.  The flag returned with the HTML
+/// tells whether any expression needs the client-side KaTeX scripts.
+///
+/// A `[TOC]` line is replaced by a table of contents, `[LOF]` / `[LOT]` by
+/// the list of figures / tables; `opts.toc`, `opts.list_of_figures` and
+/// `opts.list_of_tables` put them at the top when the document has no marker.
+/// Display equations, captioned figures and tables are numbered and their
+/// references resolved (see [`crate::crossref`]), and `[@key]` citations
+/// formatted with a reference list when `opts.bibliography` is set.
+pub fn render(content: &str, opts: &RenderOptions, engine: Option<&KatexEngine>) -> (String, bool) {
+    let (processed, mut math_exprs) = process_math_expressions(content);
+    let (processed, anchors) = resolve_equations(&processed, &mut math_exprs, opts.equation_numbers);
+    let processed = cite_references(&processed, opts);
+    let (processed, toc_marker) = replace_markers(&processed, "toc", TOC_PLACEHOLDER);
+    let (processed, lof_marker) = replace_markers(&processed, "lof", LOF_PLACEHOLDER);
+    let (processed, lot_marker) = replace_markers(&processed, "lot", LOT_PLACEHOLDER);
+    let (mut html, headings, floats) = render_markdown_with_headings(&processed, opts);
+    let lists = [
+        (toc_marker, opts.toc, TOC_PLACEHOLDER, toc_html(&headings, opts.lang.as_deref())),
+        (lof_marker, opts.list_of_figures, LOF_PLACEHOLDER, float_list_html(&floats, FloatKind::Figure)),
+        (lot_marker, opts.list_of_tables, LOT_PLACEHOLDER, float_list_html(&floats, FloatKind::Table)),
+    ];
+    let mut top = String::new();
+    for (marker, enabled, placeholder, list) in lists {
+        if marker {
+            html = html.replace(placeholder, &list);
+        } else if enabled {
+            top.push_str(&list);
+        }
+    }
+    html.insert_str(0, &top);
 
+    let mut client_math = false;
     for expr in &math_exprs {
-        let math_html = generate_math_html(&expr.content, matches!(expr.kind, MathKind::Block));
-        html = html.replacen(&expr.placeholder, &math_html, 1);
+        let is_block = matches!(expr.kind, MathKind::Block);
+        let mut math_html = match engine.map(|e| e.render(&expr.content, is_block)) {
+            Some(Ok(katex_html)) => generate_static_math_html(&katex_html, is_block),
+            Some(Err(e)) => {
+                eprintln!("Warning: KaTeX failed on `{}`: {}", expr.content, e);
+                client_math = true;
+                generate_math_html(&expr.content, is_block)
+            }
+            None => {
+                client_math = true;
+                generate_math_html(&expr.content, is_block)
+            }
+        };
+        if let Some(id) = anchors.get(&expr.placeholder) {
+            let tag = format!(r#"<div class="math-block" id="{}""#, escape_html(id));
+            math_html = math_html.replacen(r#"<div class="math-block""#, &tag, 1);
+        }
+        // The table of contents repeats math in headings.
+        html = html.replace(&expr.placeholder, &math_html);
+    }
+
+    (html, client_math)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_code_blocks_and_spans() {
+        let md = "a `$x$` b\n\n```\n$$y$$\n```\n\n    $z$\n";
+        let regions: Vec<&str> = find_code_regions(md).into_iter().map(|r| &md[r]).collect();
+        assert_eq!(regions, ["`$x$`", "```\n$$y$$\n```", "$z$\n"]);
+    }
+
+    #[test]
+    fn math_in_code_is_not_extracted() {
+        let md = "$a$ `$b$` \\(c\\)\n\n```tex\n$$d$$\n```\n\n$$e$$ \\[f\\]\n";
+        let (text, exprs) = process_math_expressions(md);
+        let contents: Vec<&str> = exprs.iter().map(|e| e.content.as_str()).collect();
+        assert_eq!(contents, ["a", "c", "e", "f"]);
+        assert!(text.contains("`$b$`") && text.contains("$$d$$"));
+        assert!(text.starts_with("<!--MATH_INLINE_0--> `$b$` <!--MATH_INLINE_1-->"));
+    }
+
+    #[test]
+    fn escaped_and_multiline_dollars() {
+        let (text, exprs) = process_math_expressions("costs \\$5, $x$ and $$1");
+        assert_eq!(exprs.len(), 1);
+        assert_eq!(text, "costs \\$5, <!--MATH_INLINE_0--> and $$1");
+
+        let (text, exprs) = process_math_expressions("$a\nb$");
+        assert!(matches!(exprs[0].kind, MathKind::Block));
+        assert_eq!(text, "<!--MATH_BLOCK_0-->");
+    }
+
+    #[test]
+    fn splits_front_matter() {
+        let md = "---\ntitle: Report\nauthor: [A, B]\nkeywords: x, y，z\nfont_size: 14\ntoc: true\n---\n# Body\n";
+        let (fm, body) = split_front_matter(md);
+        let fm = fm.unwrap();
+        assert_eq!(body, "# Body\n");
+        assert_eq!(fm.title.as_deref(), Some("Report"));
+        assert_eq!(fm.authors, ["A", "B"]);
+        assert_eq!(fm.keywords, ["x", "y", "z"]);
+        assert_eq!(fm.option("font-size"), Some("14"));
+        assert_eq!(fm.option("toc"), Some("true"));
+    }
+
+    #[test]
+    fn front_matter_edge_cases() {
+        // BOM, CRLF and a `...` terminator.
+        let (fm, body) = split_front_matter("\u{feff}---\r\nlang: zh\r\n...\r\ntext");
+        assert_eq!(fm.unwrap().lang.as_deref(), Some("zh"));
+        assert_eq!(body, "text");
+        // An empty block is still front matter.
+        let (fm, body) = split_front_matter("---\n---\ntext");
+        assert!(fm.is_some());
+        assert_eq!(body, "text");
+
+        // Not YAML mappings, unterminated or invalid: the document is kept.
+        for md in ["---\nplain text\n---\nmore", "---\ntitle: x\n", "---\n: [\n---\nx", "# no\n---\n"] {
+            let (fm, body) = split_front_matter(md);
+            assert!(fm.is_none(), "{}", md);
+            assert_eq!(body, md);
+        }
     }
 
-    html
+    #[test]
+    fn slugs_follow_github() {
+        assert_eq!(slugify("  Hello, World! "), "hello-world");
+        assert_eq!(slugify("C++ & Rust_2021"), "c--rust_2021");
+        assert_eq!(slugify("中文 标题"), "中文-标题");
+        assert_eq!(slugify("?!"), "");
+    }
+
+    #[test]
+    fn heading_ids_are_unique() {
+        let headings = [
+            (None, "Intro".to_string()),
+            (None, "Intro".to_string()),
+            (Some("intro-2".to_string()), "Custom".to_string()),
+            (None, "Intro".to_string()),
+            (None, "!!".to_string()),
+            (None, "?".to_string()),
+        ];
+        assert_eq!(heading_ids(&headings), ["intro", "intro-1", "intro-2", "intro-3", "section", "section-1"]);
+    }
+
+    #[test]
+    fn rendered_headings_carry_ids() {
+        let (html, _) = render("# Same\n\n## Same\n\n### Other {#mine}\n", &RenderOptions::default(), None);
+        assert!(html.contains(r#"<h1 id="same">"#), "{}", html);
+        assert!(html.contains(r#"<h2 id="same-1">"#), "{}", html);
+        assert!(html.contains(r#"<h3 id="mine">"#), "{}", html);
+    }
 }
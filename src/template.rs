@@ -1,192 +1,384 @@
-/// template.rs — Build the full HTML document.  Mirrors template.js.
+//! template.rs — Build the full HTML document.  Mirrors template.js.
 
 use crate::config::{
     chinese_font_family, font_size_px, font_weight_value, line_spacing_value,
-    math_spacing_value, paragraph_spacing_value, StyleOptions,
+    math_spacing_value, paragraph_spacing_value, CoverOptions, Margins, MathRender, StyleOptions,
 };
+use crate::highlight::theme_css;
+use crate::theme::{data_url, inline_urls, load_theme, mime_type, DEFAULT_THEME};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::renderer::{escape_html, is_chinese, FrontMatter};
 
 // ─────────────────────────────────────────────
 //  CSS generation
 // ─────────────────────────────────────────────
 
-/// Build the CSS block.  Mirrors `getCssStyles()` in template.js.
-pub fn get_css_styles(opts: &StyleOptions) -> String {
-    let font_size      = font_size_px(&opts.font_size);
-    let font_family    = chinese_font_family(&opts.chinese_font);
-    let font_weight_val = font_weight_value(&opts.font_weight);
-    let line_spacing_val = line_spacing_value(&opts.line_spacing);
-    let para_spacing_val = paragraph_spacing_value(&opts.paragraph_spacing);
-    let math_spacing_val = math_spacing_value(&opts.math_spacing);
-
-    // px → pt for print media (1px = 0.75pt)
-    let px_num: f64 = font_size
-        .trim_end_matches("px")
-        .parse()
-        .unwrap_or(14.0);
-    let pt_size = format!("{}pt", px_num * 0.75);
+/// Rules for markup md2pdf itself generates (code line numbers, the table of
+/// contents, figure captions) and for page breaking; they apply under every
+/// theme.
+const BASE_CSS: &str = r#"
+        .math-inline {
+            display: inline;
+        }
 
-    format!(
-        r#"
-        /* 基础样式 */
-        body {{
-            font-family: {font_family};
-            font-weight: {font_weight_val};
-            line-height: {line_spacing_val};
-            max-width: 800px;
-            margin: 0 auto;
-            padding: 20px;
-            color: #333;
-            background-color: #fff;
-            font-size: {font_size};
-        }}
-
-        /* 段落间距 */
-        p {{
-            margin-top: 0;
-            margin-bottom: {para_spacing_val};
-        }}
-
-        /* 列表项间距 */
-        li {{
-            margin-bottom: calc({para_spacing_val} * 0.5);
-        }}
-
-        /* 数学公式样式 */
-        .math-block {{
-            margin: {math_spacing_val} 0;
+        /* 代码行号 */
+        pre.line-numbers code {
+            counter-reset: code-line;
+        }
+
+        pre.line-numbers .code-line {
+            display: block;
+            position: relative;
+            padding-left: 3.5em;
+        }
+
+        pre.line-numbers .code-line::before {
+            counter-increment: code-line;
+            content: counter(code-line);
+            position: absolute;
+            left: 0;
+            width: 2.5em;
+            padding-right: 0.5em;
+            border-right: 1px solid #d0d7de;
+            text-align: right;
+            color: #8c959f;
+            user-select: none;
+        }
+
+        /* 目录样式 */
+        .toc {
+            margin: 0.5em 0 1em;
+        }
+
+        .toc-title {
+            font-size: 1.5em;
+            font-weight: 600;
+            margin-bottom: 0.5em;
+        }
+
+        .toc ul {
+            list-style: none;
+            margin: 0;
+            padding-left: 1.5em;
+        }
+
+        .toc > ul {
+            padding-left: 0;
+        }
+
+        .toc a {
+            display: flex;
+            align-items: baseline;
+            color: inherit;
+            text-decoration: none;
+        }
+
+        .toc-leader {
+            flex: 1;
+            min-width: 1em;
+            margin: 0 0.4em;
+            border-bottom: 1px dotted #999;
+            visibility: hidden;
+        }
+
+        .toc-with-pages .toc-leader {
+            visibility: visible;
+        }
+
+        /* 图表标题 */
+        figure.figure,
+        figure.table-figure {
+            margin: 1em 0;
+        }
+
+        figure.figure {
             text-align: center;
-            overflow-x: auto;
-        }}
+        }
 
-        .math-inline {{
-            display: inline;
-        }}
-
-        /* 代码样式 */
-        pre {{
-            background-color: #f6f8fa;
-            border: 1px solid #e1e4e8;
-            border-radius: 6px;
-            padding: 16px;
-            overflow-x: auto;
-            font-family: 'SFMono-Regular', Consolas, 'Liberation Mono', Menlo, monospace;
-            font-size: 14px;
-            line-height: 1.45;
-        }}
-
-        code {{
-            background-color: rgba(175, 184, 193, 0.2);
-            border-radius: 6px;
-            padding: 2px 4px;
-            font-family: 'SFMono-Regular', Consolas, 'Liberation Mono', Menlo, monospace;
-            font-size: 85%;
-        }}
-
-        pre code {{
-            background-color: transparent;
-            border-radius: 0;
-            padding: 0;
-            font-size: 100%;
-        }}
-
-        /* 表格样式 */
-        table {{
-            border-collapse: collapse;
-            margin: 25px 0;
-            font-size: 0.9em;
-            min-width: 400px;
-            border-radius: 5px 5px 0 0;
+        figure.figure img {
+            max-width: 100%;
+        }
+
+        figure figcaption {
+            font-size: 0.95em;
+            text-align: center;
+            text-indent: 0;
+        }
+
+        figure.figure figcaption {
+            margin-top: 0.5em;
+        }
+
+        figure.table-figure figcaption {
+            margin-bottom: 0.5em;
+        }
+
+        .caption-label {
+            font-weight: 600;
+        }
+
+        /* 参考文献 */
+        sup.citation-group {
+            line-height: 0;
+        }
+
+        .references .reference {
+            margin: 0.4em 0;
+        }
+
+        .references-numeric .reference,
+        .references-gb7714 .reference {
+            padding-left: 2.5em;
+            text-indent: -2.5em;
+        }
+
+        .references-author-year .reference {
+            padding-left: 2em;
+            text-indent: -2em;
+        }
+
+        .reference a {
+            word-break: break-all;
+        }
+
+        /* 提示框 */
+        .admonition {
+            margin: 1em 0;
+            padding: 0.6em 1em;
+            border: 1px solid var(--admonition-border);
+            border-left: 4px solid var(--admonition-color);
+            border-radius: 4px;
+            background: var(--admonition-background);
+            -webkit-print-color-adjust: exact;
+            print-color-adjust: exact;
+        }
+
+        .admonition-note      { --admonition-color: #0969da; --admonition-border: #b6d4fe; --admonition-background: #f1f7ff; }
+        .admonition-tip       { --admonition-color: #1a7f37; --admonition-border: #b4e2c1; --admonition-background: #f0faf3; }
+        .admonition-important { --admonition-color: #8250df; --admonition-border: #d8c7f6; --admonition-background: #f7f3fe; }
+        .admonition-warning   { --admonition-color: #9a6700; --admonition-border: #f0d68a; --admonition-background: #fff9e6; }
+        .admonition-caution   { --admonition-color: #d1242f; --admonition-border: #f5c0c3; --admonition-background: #fff3f3; }
+
+        .admonition-title {
+            display: flex;
+            align-items: center;
+            gap: 0.4em;
+            margin: 0 0 0.4em;
+            font-weight: 600;
+            color: var(--admonition-color);
+            text-indent: 0;
+        }
+
+        .admonition-icon {
+            width: 1.1em;
+            height: 1.1em;
+            flex: none;
+        }
+
+        .admonition > :last-child {
+            margin-bottom: 0;
+        }
+
+        /* 封面 */
+        .cover {
+            display: flex;
+            flex-direction: column;
+            box-sizing: border-box;
             overflow: hidden;
-            box-shadow: 0 0 20px rgba(0, 0, 0, 0.15);
-        }}
+            page-break-after: always;
+            text-indent: 0;
+        }
+
+        .cover-logo img {
+            max-width: 60%;
+            max-height: 25mm;
+        }
+
+        .cover-heading {
+            margin-bottom: auto;
+        }
+
+        .cover-title {
+            font-size: 2.4em;
+            font-weight: 700;
+            line-height: 1.3;
+        }
+
+        .cover-subtitle {
+            margin-top: 0.6em;
+            font-size: 1.4em;
+            color: #555;
+        }
+
+        .cover-organization,
+        .cover-details {
+            font-size: 1.1em;
+            line-height: 1.8;
+        }
+
+        .cover-classic {
+            align-items: center;
+            text-align: center;
+        }
+
+        .cover-classic .cover-heading {
+            margin-top: 30mm;
+        }
 
-        table thead tr {{
-            background-color: #009879;
-            color: #ffffff;
+        .cover-classic .cover-organization {
+            order: 1;
+        }
+
+        .cover-classic .cover-details {
+            order: 2;
+        }
+
+        .cover-modern {
+            padding-left: 10mm;
+            border-left: 5mm solid #2f5496;
             text-align: left;
-        }}
-
-        table th,
-        table td {{
-            padding: 12px 15px;
-            border: 1px solid #dddddd;
-        }}
-
-        table tbody tr {{
-            border-bottom: 1px solid #dddddd;
-        }}
-
-        table tbody tr:nth-of-type(even) {{
-            background-color: #f3f3f3;
-        }}
-
-        /* 引用样式 */
-        blockquote {{
-            border-left: 4px solid #dfe2e5;
-            padding: 0 16px;
-            color: #6a737d;
-            background-color: #f6f8fa;
-            margin: {para_spacing_val} 0;
-            line-height: {line_spacing_val};
-        }}
-
-        /* 标题样式 */
-        h1, h2, h3, h4, h5, h6 {{
-            margin-top: calc({para_spacing_val} * 1.5);
-            margin-bottom: {para_spacing_val};
+        }
+
+        .cover-modern .cover-heading {
+            margin-top: auto;
+        }
+
+        .cover-modern .cover-title {
+            font-size: 2.8em;
+            color: #2f5496;
+        }
+
+        .cover-formal {
+            align-items: center;
+            text-align: center;
+        }
+
+        .cover-formal .cover-organization {
+            font-size: 1.4em;
             font-weight: 600;
-            line-height: {line_spacing_val};
-        }}
+            letter-spacing: 0.1em;
+        }
 
-        h1 {{
-            font-size: 2em;
-            border-bottom: 1px solid #eaecef;
-            padding-bottom: 0.3em;
-        }}
+        .cover-formal .cover-heading {
+            margin-top: auto;
+        }
 
-        h2 {{
-            font-size: 1.5em;
-            border-bottom: 1px solid #eaecef;
-            padding-bottom: 0.3em;
-        }}
+        .cover-formal .cover-details {
+            width: 60%;
+            padding-top: 0.8em;
+            border-top: 1px solid #333;
+        }
 
-        /* 链接样式 */
-        a {{
-            color: #0366d6;
-            text-decoration: none;
-        }}
-
-        a:hover {{
-            text-decoration: underline;
-        }}
-
-        /* 打印样式 */
-        @media print {{
-            body {{
-                max-width: none;
-                margin: 0;
-                padding: 15mm;
-                font-size: {pt_size};
-            }}
-
-            .math-block {{
+        /* 打印分页 */
+        @media print {
+            .math-block {
                 page-break-inside: avoid;
-            }}
+            }
 
-            pre {{
+            pre {
                 page-break-inside: avoid;
                 white-space: pre-wrap;
-            }}
+            }
 
-            table {{
+            table,
+            figure.figure,
+            .admonition {
                 page-break-inside: avoid;
-            }}
+            }
 
-            h1, h2, h3, h4, h5, h6 {{
+            h1, h2, h3, h4, h5, h6 {
                 page-break-after: avoid;
-            }}
-        }}
-"#
+            }
+        }
+"#;
+
+/// Rules for the style options that are set, overriding the theme.
+fn style_overrides(opts: &StyleOptions) -> String {
+    let mut css = String::new();
+    if let Some(size) = &opts.font_size {
+        let size = font_size_px(size);
+        // px → pt for print media (1px = 0.75pt)
+        let print_size = match size.strip_suffix("px").and_then(|px| px.parse::<f64>().ok()) {
+            Some(px) => format!("{}pt", px * 0.75),
+            None => size.to_string(),
+        };
+        css.push_str(&format!(
+            "body {{ font-size: {size}; }}\n@media print {{ body {{ font-size: {print_size}; }} }}\n"
+        ));
+    }
+    if let Some(font) = &opts.chinese_font {
+        css.push_str(&format!("body {{ font-family: {}; }}\n", chinese_font_family(font)));
+    }
+    if let Some(weight) = &opts.font_weight {
+        css.push_str(&format!("body {{ font-weight: {}; }}\n", font_weight_value(weight)));
+    }
+    if let Some(spacing) = &opts.line_spacing {
+        css.push_str(&format!(
+            "body, h1, h2, h3, h4, h5, h6, blockquote {{ line-height: {}; }}\n",
+            line_spacing_value(spacing)
+        ));
+    }
+    if let Some(spacing) = &opts.paragraph_spacing {
+        let spacing = paragraph_spacing_value(spacing);
+        css.push_str(&format!(
+            "p {{ margin-bottom: {spacing}; }}\n\
+             li {{ margin-bottom: calc({spacing} * 0.5); }}\n\
+             blockquote {{ margin-top: {spacing}; margin-bottom: {spacing}; }}\n\
+             h1, h2, h3, h4, h5, h6 {{ margin-top: calc({spacing} * 1.5); margin-bottom: {spacing}; }}\n"
+        ));
+    }
+    if let Some(spacing) = &opts.math_spacing {
+        css.push_str(&format!(
+            ".math-block {{ margin-top: {0}; margin-bottom: {0}; }}\n",
+            math_spacing_value(spacing)
+        ));
+    }
+    css
+}
+
+/// Contents of the user stylesheets, with relative `url()`s embedded.
+/// Unreadable files are skipped with a warning.
+fn user_css(files: &[PathBuf]) -> String {
+    let mut css = String::new();
+    for path in files {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                let base = path.parent().unwrap_or(Path::new("."));
+                css.push_str(&inline_urls(&content, base));
+                css.push('\n');
+            }
+            Err(e) => eprintln!("Warning: cannot read CSS file {}: {}", path.display(), e),
+        }
+    }
+    css
+}
+
+/// Build the CSS block: the common rules, the theme, the style options set
+/// on top of it, the code highlighting theme and finally the user's own
+/// stylesheets.  An unusable theme falls back to the default one with a
+/// warning.
+pub fn get_css_styles(opts: &StyleOptions) -> String {
+    let theme = load_theme(&opts.theme).unwrap_or_else(|e| {
+        eprintln!("Warning: {}，使用默认主题", e);
+        load_theme(DEFAULT_THEME).unwrap_or_default()
+    });
+
+    format!(
+        r#"{BASE_CSS}
+        /* 主题 */
+        {theme}
+        /* 样式选项 */
+        {overrides}
+        /* 代码高亮主题 */
+        {code_theme_css}
+        /* 自定义样式 */
+        {user_css}
+"#,
+        overrides = style_overrides(opts),
+        code_theme_css = theme_css(&opts.code_theme).unwrap_or_default(),
+        user_css = user_css(&opts.css_files),
     )
 }
 
@@ -194,64 +386,347 @@ pub fn get_css_styles(opts: &StyleOptions) -> String {
 //  HTML document assembly
 // ─────────────────────────────────────────────
 
-/// Build the full HTML document.  Mirrors `generateHtmlDocument()` in template.js.
-pub fn generate_html_document(
-    content: &str,
-    title: &str,
-    katex_css: &str,
-    katex_js: &str,
-    katex_auto_render_js: &str,
-    style_opts: &StyleOptions,
-) -> String {
-    let css = get_css_styles(style_opts);
-
+/// KaTeX JS + auto-render bootstrap placed in `<head>` for client-side math.
+fn katex_head_scripts(katex_js: &str, katex_auto_render_js: &str) -> String {
     format!(
-        r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>{title}</title>
-    <style>
-        {katex_css}
-        {css}
-    </style>
-    <!-- Local KaTeX JavaScript Library (inlined) -->
+        r#"<!-- Local KaTeX JavaScript Library (inlined) -->
     <script>
     {katex_js}
     </script>
     <!-- Local KaTeX Auto-render Extension (inlined) -->
     <script>
     {katex_auto_render_js}
-    </script>
-</head>
-<body>
-        {content}
-    <script>
+    </script>"#
+    )
+}
+
+/// Auto-render call that writes the `#render-complete` sentinel when done.
+fn katex_body_script() -> &'static str {
+    r#"<script>
     // KaTeX auto-render — applied after DOM is ready
-    document.addEventListener("DOMContentLoaded", function() {{
-        try {{
-            if (typeof renderMathInElement !== 'undefined') {{
-                renderMathInElement(document.body, {{
+    document.addEventListener("DOMContentLoaded", function() {
+        try {
+            if (typeof renderMathInElement !== 'undefined') {
+                renderMathInElement(document.body, {
                     delimiters: [
-                        {{left: '$$', right: '$$', display: true}},
-                        {{left: '$', right: '$', display: false}},
-                        {{left: '\\\\(', right: '\\\\)', display: false}},
-                        {{left: '\\\\[', right: '\\\\]', display: true}}
+                        {left: '$$', right: '$$', display: true},
+                        {left: '$', right: '$', display: false},
+                        {left: '\\\\(', right: '\\\\)', display: false},
+                        {left: '\\\\[', right: '\\\\]', display: true}
                     ],
                     throwOnError: false
-                }});
-            }}
-        }} finally {{
+                });
+            }
+        } finally {
             // 写入哨兵元素，通知 Rust 端公式渲染已结束
             var done = document.createElement("div");
             done.id = "render-complete";
             done.style.display = "none";
             document.body.appendChild(done);
-        }}
-    }});
-    </script>
+        }
+    });
+    </script>"#
+}
+
+/// Math was typeset on the server: the sentinel is present from the start.
+const STATIC_RENDER_COMPLETE: &str = r#"<div id="render-complete" style="display:none"></div>"#;
+
+/// `<meta>` tags for author / date / description / keywords from the front matter.
+fn meta_tags(meta: &FrontMatter) -> String {
+    let mut tags = Vec::new();
+    if !meta.authors.is_empty() {
+        tags.push(("author", meta.authors.join(", ")));
+    }
+    if let Some(subject) = &meta.subject {
+        tags.push(("description", subject.clone()));
+    }
+    if let Some(date) = &meta.date {
+        tags.push(("date", date.clone()));
+    }
+    if !meta.keywords.is_empty() {
+        tags.push(("keywords", meta.keywords.join(", ")));
+    }
+    tags.iter()
+        .map(|(name, content)| {
+            format!(r#"<meta name="{}" content="{}">"#, name, escape_html(content))
+        })
+        .collect::<Vec<_>>()
+        .join("\n    ")
+}
+
+/// Pieces of the HTML document that a template arranges.
+struct DocumentParts {
+    title: String,
+    lang: String,
+    meta_tags: String,
+    /// `<style>` element with the KaTeX and document CSS.
+    styles: String,
+    /// `<head>` scripts (KaTeX), empty for server-side math.
+    scripts: String,
+    /// Rendered body followed by the render-complete script / sentinel.
+    content: String,
+}
+
+/// Check that a user HTML template has a `{content}` placeholder.
+pub fn validate_html_template(template: &str) -> Result<(), String> {
+    if template.contains("{content}") {
+        Ok(())
+    } else {
+        Err("HTML 模板中缺少 {content} 占位符".to_string())
+    }
+}
+
+/// Fill a user HTML template.  `{title}`, `{lang}`, `{meta}`, `{styles}`,
+/// `{scripts}` and `{content}` are replaced in a single pass, so text in the
+/// document that looks like a placeholder is left alone.  Styles and scripts
+/// the template leaves out are added before `</head>`, and the render-complete
+/// sentinel always follows the content, so math still renders and printing
+/// still knows when the page is ready.
+fn fill_html_template(template: &str, parts: &DocumentParts) -> String {
+    let mut head_extra = String::new();
+    if !template.contains("{styles}") {
+        head_extra.push_str(&parts.styles);
+    }
+    if !template.contains("{scripts}") {
+        head_extra.push_str(&parts.scripts);
+    }
+    let template = match template.find("</head>") {
+        Some(pos) if !head_extra.is_empty() => {
+            format!("{}{}\n{}", &template[..pos], head_extra, &template[pos..])
+        }
+        None if !head_extra.is_empty() => format!("{}\n{}", head_extra, template),
+        _ => template.to_string(),
+    };
+
+    let re = Regex::new(r"\{(title|lang|meta|styles|scripts|content)\}").expect("valid regex");
+    re.replace_all(&template, |cap: &regex::Captures| match &cap[1] {
+        "title"   => parts.title.clone(),
+        "lang"    => parts.lang.clone(),
+        "meta"    => parts.meta_tags.clone(),
+        "styles"  => parts.styles.clone(),
+        "scripts" => parts.scripts.clone(),
+        _ /* content */ => parts.content.clone(),
+    })
+    .into_owned()
+}
+
+/// Built-in document skeleton.
+fn default_html_document(parts: &DocumentParts) -> String {
+    let DocumentParts { title, lang, meta_tags, styles, scripts, content } = parts;
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    {meta_tags}
+    <title>{title}</title>
+    {styles}
+    {scripts}
+</head>
+<body>
+        {content}
 </body>
 </html>"#
     )
 }
+
+/// Build the full HTML document.  Mirrors `generateHtmlDocument()` in template.js.
+///
+/// `meta.title` is used as the `<title>` and `meta.lang` (default `en`) as the
+/// document language.  With [`MathRender::Server`] no script is emitted at all.
+/// `style_opts.html_template`, when set, replaces the built-in skeleton; an
+/// unreadable or invalid template falls back to it with a warning.
+pub fn generate_html_document(
+    content: &str,
+    meta: &FrontMatter,
+    katex_css: &str,
+    katex_js: &str,
+    katex_auto_render_js: &str,
+    style_opts: &StyleOptions,
+    math: MathRender,
+) -> String {
+    let css = get_css_styles(style_opts);
+    let (head_scripts, body_script) = match math {
+        MathRender::Client => (
+            katex_head_scripts(katex_js, katex_auto_render_js),
+            katex_body_script().to_string(),
+        ),
+        MathRender::Server => (String::new(), STATIC_RENDER_COMPLETE.to_string()),
+    };
+    let parts = DocumentParts {
+        title: escape_html(meta.title.as_deref().unwrap_or("")),
+        lang: escape_html(meta.lang.as_deref().unwrap_or("en")),
+        meta_tags: meta_tags(meta),
+        styles: format!("<style>\n        {katex_css}\n        {css}\n    </style>"),
+        scripts: head_scripts,
+        content: format!("{content}\n    {body_script}"),
+    };
+
+    let Some(path) = &style_opts.html_template else {
+        return default_html_document(&parts);
+    };
+    let template = fs::read_to_string(path)
+        .map_err(|e| format!("cannot read HTML template {}: {}", path.display(), e))
+        .and_then(|t| validate_html_template(&t).map(|_| t));
+    match template {
+        Ok(template) => fill_html_template(&template, &parts),
+        Err(e) => {
+            eprintln!("Warning: {}", e);
+            default_html_document(&parts)
+        }
+    }
+}
+
+// ─────────────────────────────────────────────
+//  Cover page
+// ─────────────────────────────────────────────
+
+/// Built-in cover layouts (`--cover`): everything centred, left-aligned with
+/// an accent bar, or organisation on top with a ruled block of details.
+pub const COVER_LAYOUTS: [&str; 3] = ["classic", "modern", "formal"];
+
+/// `src` for the cover logo: URLs are kept, local files are embedded.
+fn logo_src(logo: &str) -> Option<String> {
+    if logo.contains("://") || logo.starts_with("data:") {
+        return Some(logo.to_string());
+    }
+    let path = Path::new(logo);
+    let Some(mime) = mime_type(path) else {
+        eprintln!("Warning: unsupported cover logo image: {}", logo);
+        return None;
+    };
+    match data_url(path, mime) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            eprintln!("Warning: cannot read cover logo {}: {}", logo, e);
+            None
+        }
+    }
+}
+
+/// Cover page HTML, or `None` without a cover layout.  Title, authors and
+/// date come from `meta` (already defaulted from `cover`), the rest from
+/// `cover`.  `page_height` is the printable height in inches; the cover fills
+/// it, leaving room for the theme's body padding.  A custom template (a
+/// layout that is not built in) may use `{title}`, `{subtitle}`, `{authors}`,
+/// `{organization}`, `{date}`, `{logo}` (image URL) and `{version}`; one
+/// that cannot be read falls back to `classic` with a warning.
+pub fn cover_html(cover: &CoverOptions, meta: &FrontMatter, page_height: f64) -> Option<String> {
+    let name = cover.layout.as_deref()?;
+    let title = meta.title.as_deref().unwrap_or("");
+    let chinese = is_chinese(meta.lang.as_deref(), title);
+    let authors = meta.authors.join(if chinese { "、" } else { ", " });
+    let version = cover
+        .version
+        .as_deref()
+        .map(|v| format!("{}{}", if chinese { "版本 " } else { "Version " }, v));
+    let logo = cover.logo.as_deref().and_then(logo_src);
+    let style = format!("height: calc({:.2}in - 32mm)", page_height);
+
+    let mut layout = name.to_ascii_lowercase();
+    if !COVER_LAYOUTS.contains(&layout.as_str()) {
+        match fs::read_to_string(name) {
+            Ok(template) => {
+                let re = Regex::new(r"\{(title|subtitle|authors|organization|date|logo|version)\}")
+                    .expect("valid regex");
+                let body = re.replace_all(&template, |cap: &regex::Captures| {
+                    let value = match &cap[1] {
+                        "title"        => Some(title),
+                        "subtitle"     => cover.subtitle.as_deref(),
+                        "authors"      => Some(authors.as_str()),
+                        "organization" => cover.organization.as_deref(),
+                        "date"         => meta.date.as_deref(),
+                        "logo"         => logo.as_deref(),
+                        _ /* version */ => cover.version.as_deref(),
+                    };
+                    escape_html(value.unwrap_or(""))
+                });
+                return Some(format!(
+                    "<section class=\"cover cover-custom\" style=\"{}\">\n{}\n</section>\n",
+                    style, body
+                ));
+            }
+            Err(e) => {
+                eprintln!("Warning: cannot read cover template {}: {}", name, e);
+                layout = "classic".to_string();
+            }
+        }
+    }
+
+    let line = |class: &str, text: Option<&str>| match text.filter(|t| !t.is_empty()) {
+        Some(t) => format!("<div class=\"{}\">{}</div>\n", class, escape_html(t)),
+        None => String::new(),
+    };
+    let mut html = format!("<section class=\"cover cover-{}\" style=\"{}\">\n", layout, style);
+    if let Some(src) = &logo {
+        html.push_str(&format!(
+            "<div class=\"cover-logo\"><img src=\"{}\" alt=\"\"></div>\n",
+            escape_html(src)
+        ));
+    }
+    html.push_str(&line("cover-organization", cover.organization.as_deref()));
+    html.push_str("<div class=\"cover-heading\">\n");
+    html.push_str(&line("cover-title", Some(title)));
+    html.push_str(&line("cover-subtitle", cover.subtitle.as_deref()));
+    html.push_str("</div>\n<div class=\"cover-details\">\n");
+    html.push_str(&line("cover-authors", Some(&authors)));
+    html.push_str(&line("cover-version", version.as_deref()));
+    html.push_str(&line("cover-date", meta.date.as_deref()));
+    html.push_str("</div>\n</section>\n");
+    Some(html)
+}
+
+// ─────────────────────────────────────────────
+//  Page header / footer
+// ─────────────────────────────────────────────
+
+/// Whether a header / footer template needs the running section title.
+pub fn uses_section(template: Option<&str>) -> bool {
+    template.is_some_and(|t| t.contains("{section}"))
+}
+
+/// Expand a header / footer template into the HTML Chrome prints in the page
+/// margin.  `{page}` / `{pages}` become Chrome's page counters (`pages` is
+/// used instead of `{pages}` when given), `{title}` / `{date}` come from the
+/// front matter (Chrome's document title / print date otherwise) and
+/// `{section}` is replaced by `section`.
+pub fn header_footer_html(
+    template: &str,
+    meta: &FrontMatter,
+    section: Option<&str>,
+    pages: Option<u32>,
+    margins: &Margins,
+) -> String {
+    let title = match &meta.title {
+        Some(t) => escape_html(t),
+        None => r#"<span class="title"></span>"#.to_string(),
+    };
+    let date = match &meta.date {
+        Some(d) => escape_html(d),
+        None => r#"<span class="date"></span>"#.to_string(),
+    };
+    let total = match pages {
+        Some(n) => n.to_string(),
+        None => r#"<span class="totalPages"></span>"#.to_string(),
+    };
+    let section = escape_html(section.unwrap_or(""));
+    // One pass, so substituted text (a title containing `{date}`) stays as is.
+    let re = Regex::new(r"\{(page|pages|title|date|section)\}").expect("valid regex");
+    let content = re.replace_all(template, |cap: &regex::Captures| match &cap[1] {
+        "page"  => r#"<span class="pageNumber"></span>"#.to_string(),
+        "pages" => total.clone(),
+        "title" => title.clone(),
+        "date"  => date.clone(),
+        _ /* section */ => section.clone(),
+    });
+
+    // Chrome renders these at a tiny default size, without the page's styles.
+    format!(
+        r#"<div style="width: 100%; box-sizing: border-box; padding: 0 {right:.3}in 0 {left:.3}in; font-size: 9px; color: #555; font-family: {font}; -webkit-print-color-adjust: exact;">{content}</div>"#,
+        right = margins.right,
+        left = margins.left,
+        font = chinese_font_family("auto").replace('"', "'"),
+        content = content,
+    )
+}
@@ -0,0 +1,168 @@
+//! theme.rs — Document themes: the built-in stylesheets and user theme
+//!            directories (CSS files plus optional fonts).
+//!
+//! A theme directory holds one or more `*.css` files, applied in file-name
+//! order.  Font files (ttf/otf/woff/woff2) in the directory or its `fonts/`
+//! subdirectory are declared automatically, with the file stem as the
+//! `font-family` name.  Fonts and images referenced by relative `url()`s are
+//! embedded, so the generated HTML does not depend on the theme's location.
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Theme used when none is chosen: the original md2pdf look.
+pub const DEFAULT_THEME: &str = "default";
+
+/// Built-in themes (`--theme`) → stylesheet.
+const THEMES: [(&str, &str); 5] = [
+    ("default",  include_str!("themes/default.css")),
+    ("github",   include_str!("themes/github.css")),
+    ("academic", include_str!("themes/academic.css")),
+    ("minimal",  include_str!("themes/minimal.css")),
+    ("gongwen",  include_str!("themes/gongwen.css")),
+];
+
+/// Names of the built-in themes.
+pub fn theme_names() -> Vec<&'static str> {
+    THEMES.iter().map(|(name, _)| *name).collect()
+}
+
+fn builtin(name: &str) -> Option<&'static str> {
+    THEMES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, css)| *css)
+}
+
+/// Whether `name` is a built-in theme or an existing theme directory.
+pub fn theme_exists(name: &str) -> bool {
+    builtin(name).is_some() || Path::new(name).is_dir()
+}
+
+/// Stylesheet of the theme `name`: a built-in theme, or the path of a theme
+/// directory.
+pub fn load_theme(name: &str) -> Result<String, String> {
+    if let Some(css) = builtin(name) {
+        return Ok(css.to_string());
+    }
+    let dir = Path::new(name);
+    if !dir.is_dir() {
+        return Err(format!(
+            "未知主题 {} (内置主题: {}，或主题目录路径)",
+            name,
+            theme_names().join(", ")
+        ));
+    }
+    load_theme_dir(dir).map_err(|e| format!("无法读取主题目录 {}: {}", dir.display(), e))
+}
+
+// ─────────────────────────────────────────────
+//  Theme directories
+// ─────────────────────────────────────────────
+
+/// MIME type of a file a stylesheet may reference, by extension.
+pub(crate) fn mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "ttf"          => "font/ttf",
+        "otf"          => "font/otf",
+        "woff"         => "font/woff",
+        "woff2"        => "font/woff2",
+        "png"          => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif"          => "image/gif",
+        "svg"          => "image/svg+xml",
+        "webp"         => "image/webp",
+        "bmp"          => "image/bmp",
+        "ico"          => "image/x-icon",
+        "avif"         => "image/avif",
+        _ => return None,
+    })
+}
+
+/// `@font-face` `format()` hint for a font MIME type.
+fn font_format(mime: &str) -> Option<&'static str> {
+    match mime {
+        "font/ttf"   => Some("truetype"),
+        "font/otf"   => Some("opentype"),
+        "font/woff"  => Some("woff"),
+        "font/woff2" => Some("woff2"),
+        _ => None,
+    }
+}
+
+pub(crate) fn data_url(path: &Path, mime: &str) -> std::io::Result<String> {
+    Ok(format!("data:{};base64,{}", mime, B64.encode(fs::read(path)?)))
+}
+
+/// Files directly inside `dir`, sorted by name.
+fn sorted_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `@font-face` rules for the font files in `dir` and `dir/fonts`.
+fn font_faces(dir: &Path) -> std::io::Result<String> {
+    let mut css = String::new();
+    for font_dir in [dir.to_path_buf(), dir.join("fonts")] {
+        if !font_dir.is_dir() {
+            continue;
+        }
+        for path in sorted_files(&font_dir)? {
+            let Some(mime) = mime_type(&path) else { continue };
+            let Some(format) = font_format(mime) else { continue };
+            let family = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+            css.push_str(&format!(
+                "@font-face {{\n    font-family: \"{}\";\n    src: url({}) format(\"{}\");\n}}\n\n",
+                family,
+                data_url(&path, mime)?,
+                format
+            ));
+        }
+    }
+    Ok(css)
+}
+
+/// Replace relative `url()` references in `css` (resolved against `base`)
+/// with data URLs.  Remote, absolute and unreadable references are kept.
+pub(crate) fn inline_urls(css: &str, base: &Path) -> String {
+    let re = Regex::new(r#"url\(\s*(['"]?)([^'")]+)['"]?\s*\)"#).expect("valid regex");
+    re.replace_all(css, |cap: &regex::Captures| {
+        let target = cap[2].trim();
+        let is_relative = !target.contains(':') && !target.starts_with(['/', '#']);
+        let path = base.join(target.split(['?', '#']).next().unwrap_or(target));
+        match mime_type(&path).filter(|_| is_relative) {
+            Some(mime) => match data_url(&path, mime) {
+                Ok(url) => format!("url({})", url),
+                Err(_) => cap[0].to_string(),
+            },
+            None => cap[0].to_string(),
+        }
+    })
+    .into_owned()
+}
+
+fn load_theme_dir(dir: &Path) -> std::io::Result<String> {
+    let stylesheets: Vec<PathBuf> = sorted_files(dir)?
+        .into_iter()
+        .filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("css")))
+        .collect();
+    if stylesheets.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "目录中没有 .css 文件"));
+    }
+    let mut css = font_faces(dir)?;
+    for path in stylesheets {
+        css.push_str(&inline_urls(&fs::read_to_string(&path)?, dir));
+        css.push('\n');
+    }
+    Ok(css)
+}
@@ -0,0 +1,262 @@
+//! watch.rs — `--watch`: keep running and re-convert whenever an input file,
+//!            an image, theme, stylesheet, template, cover logo or
+//!            bibliography it uses, or the assets directory changes.
+
+use crate::batch::Job;
+use md2pdf_rs::renderer::{local_image_urls, percent_decode, split_front_matter};
+use md2pdf_rs::theme::theme_names;
+use md2pdf_rs::{Converter, OutputFormat};
+use notify_debouncer_mini::notify::{self, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Quiet period after the last file event before re-converting.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Canonical form used to compare watcher paths with job paths.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Files of a theme directory; none for a built-in theme.
+fn theme_files(theme: &Path) -> Vec<PathBuf> {
+    let builtin = theme_names().iter().any(|name| theme.as_os_str().eq_ignore_ascii_case(name));
+    if builtin || !theme.is_dir() {
+        return Vec::new();
+    }
+    files_below(theme)
+}
+
+/// A cover logo that is a local file rather than a URL.
+fn local_logo(logo: &str) -> Option<&Path> {
+    (!logo.contains("://") && !logo.starts_with("data:")).then(|| Path::new(logo))
+}
+
+/// Files `input` is built from besides itself: the local images it
+/// references, the converter's theme directory, `--css`, `--template`, cover
+/// template, logo and `--bibliography` files, and the same keys of its front
+/// matter (`header` / `footer` templates too).  Front-matter paths are
+/// resolved the way the converter reads them: `theme`, `css`, `template`,
+/// `bibliography` and `logo` against the input's directory.
+fn referenced_files(input: &Path, converter: &Converter) -> HashSet<PathBuf> {
+    let style = converter.style_options();
+    let render = converter.render_options();
+    let mut files: Vec<PathBuf> = style
+        .css_files
+        .iter()
+        .chain(&style.html_template)
+        .chain(&render.bibliography)
+        .cloned()
+        .collect();
+    files.extend(theme_files(Path::new(&style.theme)));
+    files.extend(render.cover.layout.as_deref().map(PathBuf::from).filter(|p| p.is_file()));
+    files.extend(render.cover.logo.as_deref().and_then(local_logo).map(Path::to_path_buf));
+
+    if let Ok(markdown) = fs::read_to_string(input) {
+        let base = input.parent().unwrap_or(Path::new("."));
+        files.extend(
+            local_image_urls(&markdown)
+                .iter()
+                .filter_map(|url| url.split(['?', '#']).next())
+                .map(|url| base.join(percent_decode(url))),
+        );
+        if let (Some(fm), _) = split_front_matter(&markdown) {
+            for key in ["css", "template", "bibliography"] {
+                files.extend(fm.option(key).map(|path| base.join(path)));
+            }
+            if let Some(theme) = fm.option("theme") {
+                files.extend(theme_files(&base.join(theme)));
+            }
+            files.extend(fm.option("logo").and_then(local_logo).map(|logo| base.join(logo)));
+            for key in ["cover", "header", "footer"] {
+                files.extend(fm.option(key).map(PathBuf::from).filter(|p| p.is_file()));
+            }
+        }
+    }
+    files.iter().map(|p| canonical(p)).collect()
+}
+
+/// Modification times of `paths`.  Watchers also report reads (including our
+/// own), so a conversion only runs when this fingerprint actually changes.
+fn fingerprint<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) -> Vec<Option<SystemTime>> {
+    let mut paths: Vec<&PathBuf> = paths.into_iter().collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// All files below `dir`, for fingerprinting the assets directory.
+fn files_below(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        for entry in fs::read_dir(&d).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// A job plus the files whose changes trigger it.
+struct WatchedJob {
+    job: Job,
+    input: PathBuf,
+    files: HashSet<PathBuf>,
+    stamp: Vec<Option<SystemTime>>,
+}
+
+impl WatchedJob {
+    fn current_stamp(&self) -> Vec<Option<SystemTime>> {
+        fingerprint(std::iter::once(&self.input).chain(&self.files))
+    }
+}
+
+/// Watch directories non-recursively (editors often save by renaming a temp
+/// file over the original, which drops watches on the file itself).
+struct DirWatcher {
+    debouncer: Debouncer<RecommendedWatcher>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl DirWatcher {
+    fn watch_parent_of(&mut self, file: &Path) {
+        if let Some(dir) = file.parent() {
+            self.watch(dir, RecursiveMode::NonRecursive);
+        }
+    }
+
+    fn watch(&mut self, dir: &Path, mode: RecursiveMode) {
+        if !dir.is_dir() || !self.dirs.insert(dir.to_path_buf()) {
+            return;
+        }
+        if let Err(e) = self.debouncer.watcher().watch(dir, mode) {
+            eprintln!("Warning: cannot watch {}: {}", dir.display(), e);
+        }
+    }
+}
+
+fn convert(converter: &Converter, job: &Job, format: OutputFormat) {
+    let start = Instant::now();
+    if let Some(dir) = job.output.parent() {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("转换失败: 无法创建输出目录 {}: {}", dir.display(), e);
+            return;
+        }
+    }
+    match converter.convert_file(&job.input, &job.output, format) {
+        Ok(()) => status!(
+            "已更新: {} ({} ms)",
+            job.output.display(),
+            start.elapsed().as_millis()
+        ),
+        Err(e) => eprintln!("转换失败: {}: {}", job.input.display(), e),
+    }
+}
+
+/// Convert every job once, then re-convert affected jobs on each change until
+/// the process is interrupted.  KaTeX assets and the browser stay loaded; the
+/// assets are only re-read when something in the assets directory changes.
+/// `templates` are the `--header` and `--footer` template files, whose
+/// contents the converter holds; they are re-read when they change.
+pub fn run(
+    mut converter: Converter,
+    jobs: Vec<Job>,
+    format: OutputFormat,
+    templates: [Option<PathBuf>; 2],
+) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel::<DebounceEventResult>();
+    let mut watcher = DirWatcher {
+        debouncer: new_debouncer(DEBOUNCE, tx)?,
+        dirs: HashSet::new(),
+    };
+
+    let assets_dir = canonical(converter.assets_dir());
+    watcher.watch(&assets_dir, RecursiveMode::Recursive);
+    let mut assets_stamp = fingerprint(&files_below(&assets_dir));
+
+    let templates = templates.map(|t| t.map(|p| canonical(&p)));
+    templates.iter().flatten().for_each(|t| watcher.watch_parent_of(t));
+    let mut templates_stamp = fingerprint(templates.iter().flatten());
+
+    let mut watched: Vec<WatchedJob> = Vec::new();
+    for job in jobs {
+        convert(&converter, &job, format);
+        let input = canonical(&job.input);
+        let files = referenced_files(&input, &converter);
+        watcher.watch_parent_of(&input);
+        files.iter().for_each(|f| watcher.watch_parent_of(f));
+        let mut w = WatchedJob { job, input, files, stamp: Vec::new() };
+        w.stamp = w.current_stamp();
+        watched.push(w);
+    }
+
+    status!("\n正在监视文件变化 (Ctrl+C 退出)...");
+
+    for result in rx {
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("Warning: watch error: {}", e);
+                continue;
+            }
+        };
+        let changed: HashSet<PathBuf> = events.iter().map(|e| canonical(&e.path)).collect();
+
+        let mut rebuild_all = false;
+        if changed.iter().any(|p| p.starts_with(&assets_dir)) {
+            let stamp = fingerprint(&files_below(&assets_dir));
+            if stamp != assets_stamp {
+                status!("KaTeX 资源已变化，重新加载...");
+                converter.reload_assets();
+                assets_stamp = stamp;
+                rebuild_all = true;
+            }
+        }
+        if templates.iter().flatten().any(|t| changed.contains(t)) {
+            let stamp = fingerprint(templates.iter().flatten());
+            if stamp != templates_stamp {
+                status!("页眉/页脚模板已变化，重新加载...");
+                let mut pdf = converter.pdf_options().clone();
+                let targets = [&mut pdf.header_template, &mut pdf.footer_template];
+                for (file, template) in templates.iter().zip(targets) {
+                    let Some(file) = file else { continue };
+                    match fs::read_to_string(file) {
+                        Ok(t) => *template = Some(t),
+                        Err(e) => eprintln!("Warning: cannot read {}: {}", file.display(), e),
+                    }
+                }
+                converter.set_pdf_options(pdf);
+                templates_stamp = stamp;
+                rebuild_all = true;
+            }
+        }
+
+        for w in &mut watched {
+            if !rebuild_all {
+                if !changed.contains(&w.input) && w.files.is_disjoint(&changed) {
+                    continue;
+                }
+                if w.current_stamp() == w.stamp {
+                    continue;
+                }
+            }
+            convert(&converter, &w.job, format);
+            w.files = referenced_files(&w.input, &converter);
+            w.files.iter().for_each(|f| watcher.watch_parent_of(f));
+            w.stamp = w.current_stamp();
+        }
+    }
+
+    Ok(())
+}